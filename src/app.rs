@@ -1,14 +1,56 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet, hash_map::Entry},
+    path::Path,
+    sync::Arc,
+    time::Instant,
+};
 
 use winit::{
     application::ApplicationHandler,
     dpi::PhysicalSize,
-    event::{ElementState, KeyEvent, WindowEvent},
+    event::{DeviceEvent, ElementState, KeyEvent, MouseButton, WindowEvent},
+    event_loop::ControlFlow,
     keyboard::{KeyCode, PhysicalKey},
-    window::{Window, WindowAttributes},
+    window::{CursorGrabMode, Window, WindowAttributes},
+};
+
+use anyhow::Context;
+use glam::Vec3;
+
+use crate::{
+    block::{AIR, BlockId, BlockRegistry},
+    camera::{Camera, CameraBinding, CameraController, CameraMode, Projection},
+    chunk::{MeshMode, neighbour_offset},
+    cull::{CullGroup, CullPipeline},
+    light::{Lighting, LightingBinding},
+    lightmap,
+    memory::GpuMemory,
+    outline::Outline,
+    player::Player,
+    raycast::{RaycastHit, raycast},
+    render::{
+        DEPTH_FORMAT, FaceGroup, FaceGroupLayouts, Render, RenderPath, create_depth_texture,
+        create_shader_module,
+    },
+    resources::{ASSETS_DIR, ResourcePack},
+    shadow::ShadowMap,
+    sky::Sky,
+    texture::TextureAtlas,
+    timing::{FrameLimiter, Timestep},
+    transparency::Transparency,
+    world::{ChunkPos, World},
 };
 
-use crate::render::{FaceGroup, Render, VERTEX_BUFFER_SIZE};
+/// Position of the lamp in the demo world, switched on and off with L.
+const DEMO_LAMP: [i32; 3] = [-2, 2, -2];
+/// Furthest a block can be removed or placed from the camera, in blocks.
+const REACH: f32 = 8.0;
+/// Simulation ticks per second, so the player moves the same at any frame rate.
+const TICK_RATE: u32 = 60;
+/// Longest frame caught up on, so a stall doesn't run a burst of ticks.
+const MAX_FRAME_TIME: f32 = 0.25;
+/// Frame rate caps cycled through with F7, starting uncapped.
+const FRAME_CAPS: [Option<u32>; 4] = [None, Some(30), Some(60), Some(144)];
 
 #[derive(Default, Debug)]
 pub struct App {
@@ -27,21 +69,65 @@ pub struct AppState {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
 
+    // World
+    pub registry: BlockRegistry,
+    pub world: World,
+    pub mesh_mode: MeshMode,
+    /// Culls faces from voxels on the GPU instead of meshing chunks on the CPU.
+    pub gpu_culling: bool,
+
+    // Camera
+    pub camera: Camera,
+    pub camera_controller: CameraController,
+    pub camera_binding: CameraBinding,
+    /// Set while the cursor is captured for mouse look.
+    pub cursor_grabbed: bool,
+    /// Block placed with the right mouse button.
+    pub selected_block: BlockId,
+    /// Walks through the world in `CameraMode::Walk`.
+    pub player: Player,
+
+    // Lighting
+    pub lighting: Lighting,
+    pub lighting_binding: LightingBinding,
+    pub shadow_map: ShadowMap,
+    pub sky: Sky,
+    pub outline: Outline,
+    pub transparency: Transparency,
+
     // Render State
     pub compute_pipeline: wgpu::ComputePipeline,
-    pub face_group: FaceGroup,
+    pub face_group_layouts: FaceGroupLayouts,
+    pub memory: GpuMemory,
+    pub face_groups: HashMap<ChunkPos, FaceGroup>,
+    pub cull_pipeline: CullPipeline,
+    pub cull_groups: HashMap<ChunkPos, CullGroup>,
+    pub render_path: RenderPath,
     pub render_pipeline: wgpu::RenderPipeline,
-    pub vertex_buffer: Arc<wgpu::Buffer>,
+    pub pull_pipeline: wgpu::RenderPipeline,
+    pub transparent_pipeline: wgpu::RenderPipeline,
+    pub transparent_pull_pipeline: wgpu::RenderPipeline,
+    pub atlas: TextureAtlas,
+    pub depth_view: wgpu::TextureView,
+
+    // Frame Timing
+    pub frame_count: u32,
+    pub frame_timer: Instant,
+    pub last_frame: Instant,
+    pub timestep: Timestep,
+    pub frame_limiter: FrameLimiter,
 }
 
 impl AppState {
-    pub async fn new(window: Arc<Window>) -> AppState {
+    pub async fn new(window: Arc<Window>) -> anyhow::Result<AppState> {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             backends: wgpu::Backends::PRIMARY,
             ..Default::default()
         });
 
-        let surface = instance.create_surface(window.clone()).unwrap();
+        let surface = instance
+            .create_surface(window.clone())
+            .context("failed to create surface")?;
 
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
@@ -50,7 +136,7 @@ impl AppState {
                 force_fallback_adapter: false,
             })
             .await
-            .unwrap();
+            .context("no compatible graphics adapter")?;
 
         let (device, queue) = adapter
             .request_device(
@@ -62,35 +148,100 @@ impl AppState {
                 None,
             )
             .await
-            .unwrap();
+            .context("failed to create device")?;
 
         let surface_format = surface.get_capabilities(&adapter).formats[0];
 
         let size = window.inner_size();
 
-        let vertex_buffer = Arc::new(device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Vertex Buffer"),
-            size: VERTEX_BUFFER_SIZE,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
-            mapped_at_creation: false,
-        }));
+        let pack = ResourcePack::load(Path::new(ASSETS_DIR))?;
+        let registry = pack.registry;
+        let block = |name| {
+            registry
+                .id(name)
+                .with_context(|| format!("the demo world needs a {name} block"))
+        };
+        let (grass, stone, lamp, glass) = (
+            block("grass")?,
+            block("stone")?,
+            block("lamp")?,
+            block("glass")?,
+        );
 
-        let face_group = FaceGroup::new(&device, vertex_buffer.clone());
+        // Straddles the four chunks around the origin
+        let mut world = World::default();
+        for z in -2..2 {
+            for x in -2..2 {
+                world.set(x, 0, z, stone);
+                world.set(x, 1, z, grass);
+            }
+        }
+        // A glass wall across the far side, to look through at the rest of the world
+        for z in -2..2 {
+            for y in 2..4 {
+                world.set(1, y, z, glass);
+            }
+        }
+        let [x, y, z] = DEMO_LAMP;
+        world.set(x, y, z, lamp);
+        lightmap::relight(&mut world, &registry);
 
-        let compute_shader = device.create_shader_module(wgpu::include_wgsl!("./geometry.wgsl"));
-        let render_shader = device.create_shader_module(wgpu::include_wgsl!("./shader.wgsl"));
+        let face_group_layouts = FaceGroupLayouts::new(&device);
+        let cull_pipeline = CullPipeline::new(&device, &registry);
+        let memory = GpuMemory::new(&device);
+        let camera_layout = CameraBinding::create_bind_group_layout(&device);
+        let camera_binding = CameraBinding::new(&device, &camera_layout);
+
+        // Software rasterisers are too slow for the shadow passes
+        let mut shadow_map = ShadowMap::new(&device, &face_group_layouts.faces, &camera_layout);
+        shadow_map.enabled = adapter.get_info().device_type != wgpu::DeviceType::Cpu;
+        let lighting_layout = LightingBinding::create_bind_group_layout(&device);
+        let lighting_binding = LightingBinding::new(&device, &lighting_layout, &shadow_map);
+        let texture_layout = TextureAtlas::create_bind_group_layout(&device);
+        let atlas = TextureAtlas::new(&device, &queue, &texture_layout, &pack.atlas)
+            .context("failed to upload the texture atlas")?;
+
+        // Above the corner of the demo blocks, looking down at them
+        let camera = Camera::new(
+            Vec3::new(-6.0, 5.0, -6.0),
+            45f32.to_radians(),
+            -30f32.to_radians(),
+            size.width as f32 / size.height.max(1) as f32,
+        );
+
+        // Both need the face decoding in face.wgsl
+        let compute_shader = create_shader_module(
+            &device,
+            "geometry.wgsl",
+            &[include_str!("./face.wgsl"), include_str!("./geometry.wgsl")],
+        );
+        let render_shader = create_shader_module(
+            &device,
+            "shader.wgsl",
+            &[
+                include_str!("./face.wgsl"),
+                include_str!("./lighting.wgsl"),
+                include_str!("./shader.wgsl"),
+            ],
+        );
 
         let compute_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Compute Pipeline Layout"),
-                bind_group_layouts: &[&face_group.bind_group_layout],
+                bind_group_layouts: &[&face_group_layouts.faces, &face_group_layouts.vertices],
                 push_constant_ranges: &[],
             });
 
+        // Both render paths share the face group bindings, only vertex pulling reads them
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[],
+                bind_group_layouts: &[
+                    &face_group_layouts.faces,
+                    &camera_layout,
+                    &texture_layout,
+                    &lighting_layout,
+                ],
                 push_constant_ranges: &[],
             });
 
@@ -103,68 +254,185 @@ impl AppState {
             cache: None,
         });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &render_shader,
-                entry_point: Some("vs_main"),
-                compilation_options: Default::default(),
-                buffers: &[wgpu::VertexBufferLayout {
-                    array_stride: std::mem::size_of::<f32>() as u64 * 3,
-                    step_mode: wgpu::VertexStepMode::Vertex,
-                    attributes: &[wgpu::VertexAttribute {
-                        format: wgpu::VertexFormat::Float32x3,
-                        offset: 0,
-                        shader_location: 0,
-                    }],
-                }],
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::PointList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &render_shader,
-                entry_point: Some("fs_main"),
-                compilation_options: Default::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            multiview: None,
-            cache: None,
-        });
+        // Transparent faces are accumulated by `Transparency` instead of drawn over the surface,
+        // and leave the depth buffer to the opaque faces so that they don't hide each other
+        let create_render_pipeline = |label: &str,
+                                      layout: &wgpu::PipelineLayout,
+                                      vertex_entry_point: &str,
+                                      buffers: &[wgpu::VertexBufferLayout],
+                                      transparent: bool| {
+            let (fragment_entry_point, targets) = if transparent {
+                ("fs_transparent", Transparency::color_targets().to_vec())
+            } else {
+                (
+                    "fs_main",
+                    vec![Some(wgpu::ColorTargetState {
+                        format: surface_format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                )
+            };
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &render_shader,
+                    entry_point: Some(vertex_entry_point),
+                    compilation_options: Default::default(),
+                    buffers,
+                },
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    unclipped_depth: false,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DEPTH_FORMAT,
+                    depth_write_enabled: !transparent,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &render_shader,
+                    entry_point: Some(fragment_entry_point),
+                    compilation_options: Default::default(),
+                    targets: &targets,
+                }),
+                multiview: None,
+                cache: None,
+            })
+        };
 
-        let state = Self {
+        let vertex_buffers = [wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<f32>() as u64 * 11,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x3,
+                    offset: 0,
+                    shader_location: 0,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x2,
+                    offset: std::mem::size_of::<f32>() as u64 * 3,
+                    shader_location: 1,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x3,
+                    offset: std::mem::size_of::<f32>() as u64 * 5,
+                    shader_location: 2,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Uint32,
+                    offset: std::mem::size_of::<f32>() as u64 * 8,
+                    shader_location: 3,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32,
+                    offset: std::mem::size_of::<f32>() as u64 * 9,
+                    shader_location: 4,
+                },
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Uint32,
+                    offset: std::mem::size_of::<f32>() as u64 * 10,
+                    shader_location: 5,
+                },
+            ],
+        }];
+        let render_pipeline = create_render_pipeline(
+            "Render Pipeline",
+            &render_pipeline_layout,
+            "vs_main",
+            &vertex_buffers,
+            false,
+        );
+        let transparent_pipeline = create_render_pipeline(
+            "Transparent Render Pipeline",
+            &render_pipeline_layout,
+            "vs_main",
+            &vertex_buffers,
+            true,
+        );
+        let pull_pipeline = create_render_pipeline(
+            "Pull Pipeline",
+            &render_pipeline_layout,
+            "vs_pull",
+            &[],
+            false,
+        );
+        let transparent_pull_pipeline = create_render_pipeline(
+            "Transparent Pull Pipeline",
+            &render_pipeline_layout,
+            "vs_pull",
+            &[],
+            true,
+        );
+
+        let sky = Sky::new(&device, &camera_layout, &lighting_layout, surface_format);
+        let outline = Outline::new(&device, &camera_layout, surface_format);
+
+        let transparency = Transparency::new(&device, size.width, size.height, surface_format);
+
+        let depth_view = create_depth_texture(&device, size.width, size.height);
+
+        let mut state = Self {
             window,
             device,
             queue,
             size,
             surface,
             surface_format,
+            registry,
+            world,
+            mesh_mode: MeshMode::Greedy,
+            gpu_culling: false,
+            player: Player::new(camera.position),
+            camera,
+            camera_controller: CameraController::default(),
+            camera_binding,
+            cursor_grabbed: false,
+            selected_block: stone,
+            lighting: Lighting::default(),
+            lighting_binding,
+            shadow_map,
+            sky,
+            outline,
+            transparency,
             compute_pipeline,
-            face_group,
+            face_group_layouts,
+            memory,
+            face_groups: HashMap::new(),
+            cull_pipeline,
+            cull_groups: HashMap::new(),
+            render_path: RenderPath::Compute,
             render_pipeline,
-            vertex_buffer,
+            pull_pipeline,
+            transparent_pipeline,
+            transparent_pull_pipeline,
+            atlas,
+            depth_view,
+            frame_count: 0,
+            frame_timer: Instant::now(),
+            last_frame: Instant::now(),
+            timestep: Timestep::new(TICK_RATE),
+            frame_limiter: FrameLimiter::new(None),
         };
 
         state.configure_surface();
+        state.mesh_world();
 
-        state
+        Ok(state)
     }
 
     pub fn configure_surface(&self) {
@@ -181,10 +449,186 @@ impl AppState {
         self.surface.configure(&self.device, &surface_config);
     }
 
+    /// Rebuilds the face group of every chunk in the world.
+    pub fn mesh_world(&mut self) {
+        for (_, face_group) in self.face_groups.drain() {
+            face_group.release(&self.device, &self.queue, &mut self.memory);
+        }
+        self.cull_groups.clear();
+
+        let positions: Vec<ChunkPos> = self.world.chunks().map(|(&pos, _)| pos).collect();
+        for pos in positions {
+            self.mesh_chunk(pos);
+        }
+
+        tracing::info!(
+            "GPU memory: {} of {} KiB in use",
+            self.memory.used() / 1024,
+            self.memory.capacity() / 1024
+        );
+    }
+
+    /// Rebuilds the faces of one chunk, dropping its groups if the chunk isn't loaded or its
+    /// faces don't fit into the GPU memory.
+    pub fn mesh_chunk(&mut self, pos: ChunkPos) {
+        if self.world.chunk(pos).is_some() {
+            let Err(err) = self.upload_chunk(pos) else {
+                return;
+            };
+            tracing::error!("Skipping chunk {pos:?}: {err:#}");
+        }
+
+        if let Some(face_group) = self.face_groups.remove(&pos) {
+            face_group.release(&self.device, &self.queue, &mut self.memory);
+        }
+        self.cull_groups.remove(&pos);
+    }
+
+    /// Uploads a loaded chunk to its groups. The groups are kept across rebuilds and only grown
+    /// when the chunk's faces may no longer fit.
+    fn upload_chunk(&mut self, pos: ChunkPos) -> anyhow::Result<()> {
+        let chunk = self.world.chunk(pos).context("chunk isn't loaded")?;
+
+        if self.gpu_culling {
+            // Every block shows at most all of its faces
+            let capacity = chunk.block_count() as u32 * 6;
+            match self.face_groups.get_mut(&pos) {
+                Some(face_group) => {
+                    face_group.reserve(&self.device, &self.queue, &mut self.memory, capacity)?;
+                }
+                None => {
+                    let face_group = FaceGroup::new(
+                        &self.device,
+                        &self.queue,
+                        &self.face_group_layouts,
+                        &mut self.memory,
+                        pos.origin(),
+                        capacity,
+                        self.render_path,
+                    )?;
+                    self.face_groups.insert(pos, face_group);
+                }
+            }
+            self.cull_groups
+                .entry(pos)
+                .or_insert_with(|| CullGroup::new(&self.device))
+                .set_voxels(&self.queue, &self.world, pos);
+        } else {
+            let face_data = self.world.face_data(pos, &self.registry, self.mesh_mode);
+            let face_group = match self.face_groups.entry(pos) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(FaceGroup::new(
+                    &self.device,
+                    &self.queue,
+                    &self.face_group_layouts,
+                    &mut self.memory,
+                    pos.origin(),
+                    face_data.iter().map(|faces| faces.len() as u32).sum(),
+                    self.render_path,
+                )?),
+            };
+            face_group.set_face_data(&self.device, &self.queue, &mut self.memory, &face_data)?;
+        }
+        Ok(())
+    }
+
+    /// Sets a block and relights the world around it, then rebuilds every chunk whose faces
+    /// could have changed: those whose blocks or light changed, and their neighbours, which
+    /// cull, occlude and light their border faces with them.
+    pub fn set_block(&mut self, pos: [i32; 3], block: BlockId) {
+        let changed = lightmap::set_block(&mut self.world, &self.registry, pos, block);
+        let mut dirty = HashSet::new();
+        for pos in changed {
+            dirty.extend((0..27).map(|index| pos.offset(neighbour_offset(index))));
+        }
+        for pos in dirty {
+            self.mesh_chunk(pos);
+        }
+    }
+
+    /// The block the camera looks at, within reach.
+    pub fn target(&self) -> Option<RaycastHit> {
+        raycast(
+            &self.world,
+            self.camera.position,
+            self.camera.forward(),
+            REACH,
+        )
+    }
+
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
         self.size = new_size;
+        self.camera.aspect = new_size.width as f32 / new_size.height.max(1) as f32;
+        self.depth_view = create_depth_texture(&self.device, new_size.width, new_size.height);
+        self.transparency
+            .resize(&self.device, new_size.width, new_size.height);
         self.configure_surface();
     }
+
+    /// Captures the cursor for mouse look, or releases it.
+    pub fn set_cursor_grabbed(&mut self, grabbed: bool) {
+        if grabbed {
+            // Not every platform supports locking, confining still keeps the cursor inside
+            if let Err(err) = self
+                .window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Confined))
+            {
+                tracing::warn!("Failed to grab cursor: {err}");
+                return;
+            }
+        } else if let Err(err) = self.window.set_cursor_grab(CursorGrabMode::None) {
+            tracing::warn!("Failed to release cursor: {err}");
+        }
+        self.window.set_cursor_visible(!grabbed);
+        self.cursor_grabbed = grabbed;
+    }
+
+    /// Switches between flying and walking, starting the player where the camera is.
+    pub fn toggle_walking(&mut self) {
+        let mode = if self.camera_controller.mode == CameraMode::Walk {
+            CameraMode::Fly
+        } else {
+            self.player = Player::new(self.camera.position);
+            CameraMode::Walk
+        };
+        self.camera_controller.set_mode(&self.camera, mode);
+        tracing::info!("Camera mode: {:?}", mode);
+    }
+
+    /// Advances the simulation by one fixed tick.
+    pub fn tick(&mut self) {
+        if self.camera_controller.mode == CameraMode::Walk {
+            let direction = self.camera_controller.walk_direction(&self.camera);
+            let jump = self.camera_controller.jumping();
+            self.player
+                .step(&self.world, direction, jump, self.timestep.tick());
+        }
+    }
+
+    /// Runs the simulation ticks due since the last frame, and uploads the camera with the
+    /// lighting, shadow cascades and the outline of the block it looks at. The free camera
+    /// moves by the frame time even while the simulation is paused, the walking camera follows
+    /// the player's eyes between its last two ticks.
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        let dt = (now - self.last_frame).as_secs_f32().min(MAX_FRAME_TIME);
+        self.last_frame = now;
+
+        self.camera_controller.update(&mut self.camera, dt);
+        for _ in 0..self.timestep.advance(dt) {
+            self.tick();
+        }
+        if self.camera_controller.mode == CameraMode::Walk {
+            self.camera.position = self.player.interpolated_eye(self.timestep.alpha());
+        }
+        self.camera_binding.update(&self.queue, &self.camera);
+        self.lighting_binding.update(&self.queue, &self.lighting);
+        self.shadow_map
+            .update(&self.queue, &self.camera, self.lighting.sun_direction());
+        let target = self.target().map(|hit| hit.pos);
+        self.outline.update(&self.queue, target);
+    }
 }
 
 impl ApplicationHandler for App {
@@ -196,8 +640,14 @@ impl ApplicationHandler for App {
                 .unwrap(),
         );
 
-        let state = pollster::block_on(AppState::new(window.clone()));
-        self.state = Some(state);
+        match pollster::block_on(AppState::new(window.clone())) {
+            Ok(state) => self.state = Some(state),
+            Err(err) => {
+                tracing::error!("{err:#}");
+                event_loop.exit();
+                return;
+            }
+        }
 
         window.request_redraw();
     }
@@ -208,10 +658,12 @@ impl ApplicationHandler for App {
         _window_id: winit::window::WindowId,
         event: winit::event::WindowEvent,
     ) {
-        let state = self.state.as_mut().unwrap();
+        let Some(state) = self.state.as_mut() else {
+            return;
+        };
         match event {
-            WindowEvent::CloseRequested
-            | WindowEvent::KeyboardInput {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
                         physical_key: PhysicalKey::Code(KeyCode::Escape),
@@ -220,14 +672,306 @@ impl ApplicationHandler for App {
                     },
                 ..
             } => {
-                event_loop.exit();
+                // The first press only gives the cursor back
+                if state.cursor_grabbed {
+                    state.set_cursor_grabbed(false);
+                } else {
+                    event_loop.exit();
+                }
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyG),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                state.mesh_mode = match state.mesh_mode {
+                    MeshMode::Naive => MeshMode::Greedy,
+                    MeshMode::Greedy => MeshMode::Naive,
+                };
+                tracing::info!("Mesh mode: {:?}", state.mesh_mode);
+                state.mesh_world();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyC),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                state.gpu_culling = !state.gpu_culling;
+                tracing::info!("GPU culling: {}", state.gpu_culling);
+                state.mesh_world();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyV),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                state.render_path = match state.render_path {
+                    RenderPath::Compute => RenderPath::VertexPulling,
+                    RenderPath::VertexPulling => RenderPath::Compute,
+                };
+                tracing::info!("Render path: {:?}", state.render_path);
+                state.mesh_world();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyL),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                let [x, y, z] = DEMO_LAMP;
+                let lamp = state
+                    .registry
+                    .id("lamp")
+                    .expect("checked when the world is built");
+                let block = if state.world.get(x, y, z) == lamp {
+                    AIR
+                } else {
+                    lamp
+                };
+                state.set_block(DEMO_LAMP, block);
+                tracing::info!("Lamp: {}", block == lamp);
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyH),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                state.shadow_map.enabled = !state.shadow_map.enabled;
+                tracing::info!("Shadows: {}", state.shadow_map.enabled);
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::Tab),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                let mode = match state.camera_controller.mode {
+                    CameraMode::Fly | CameraMode::Walk => CameraMode::Orbit,
+                    CameraMode::Orbit => CameraMode::Fly,
+                };
+                state.camera_controller.set_mode(&state.camera, mode);
+                tracing::info!("Camera mode: {:?}", mode);
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyF),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => state.toggle_walking(),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F5),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                state.timestep.paused = !state.timestep.paused;
+                tracing::info!("Simulation paused: {}", state.timestep.paused);
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F6),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => state.timestep.step(),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F7),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                let current = FRAME_CAPS
+                    .iter()
+                    .position(|&cap| cap == state.frame_limiter.max_fps())
+                    .unwrap_or(0);
+                let cap = FRAME_CAPS[(current + 1) % FRAME_CAPS.len()];
+                state.frame_limiter.set_max_fps(cap);
+                match cap {
+                    Some(fps) => tracing::info!("Frame rate cap: {fps}"),
+                    None => tracing::info!("Frame rate cap: off"),
+                }
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyP),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                state.camera.projection = match state.camera.projection {
+                    Projection::Perspective => Projection::Orthographic,
+                    Projection::Orthographic => Projection::Perspective,
+                };
+                tracing::info!("Projection: {:?}", state.camera.projection);
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::KeyI),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                state.camera_controller.set_isometric(&mut state.camera);
+                tracing::info!("Isometric view");
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key:
+                            PhysicalKey::Code(key @ (KeyCode::BracketLeft | KeyCode::BracketRight)),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                let hours = if key == KeyCode::BracketLeft {
+                    -1.0
+                } else {
+                    1.0
+                };
+                state.lighting.advance(hours);
+                let minutes = (state.lighting.time_of_day * 24.0 * 60.0).round() as u32;
+                tracing::info!("Time of day: {:02}:{:02}", minutes / 60, minutes % 60);
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state: key_state,
+                        ..
+                    },
+                ..
+            } => {
+                state.camera_controller.process_keyboard(key, key_state);
+            }
+            WindowEvent::MouseWheel { delta, .. } => state.camera_controller.process_scroll(delta),
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if !state.cursor_grabbed => state.set_cursor_grabbed(true),
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button,
+                ..
+            } => {
+                let Some(hit) = state.target() else {
+                    return;
+                };
+                match button {
+                    MouseButton::Left => state.set_block(hit.pos, AIR),
+                    MouseButton::Right => {
+                        // Never into the block the camera or the walking player is in
+                        let pos = hit.front();
+                        let camera = state.camera.position.floor().as_ivec3().to_array();
+                        let walking = state.camera_controller.mode == CameraMode::Walk;
+                        if state.world.get(pos[0], pos[1], pos[2]) == AIR
+                            && pos != camera
+                            && !(walking && state.player.intersects(pos))
+                        {
+                            state.set_block(pos, state.selected_block);
+                        }
+                    }
+                    MouseButton::Middle => {
+                        state.selected_block = hit.block;
+                        let name = &state.registry.get(hit.block).name;
+                        tracing::info!("Selected block: {name}");
+                    }
+                    _ => {}
+                }
+            }
+            WindowEvent::Focused(false) => {
+                state.camera_controller.reset();
+                state.set_cursor_grabbed(false);
             }
             WindowEvent::Resized(size) => state.resize(size),
             WindowEvent::RedrawRequested => {
+                state.frame_limiter.frame_started();
+                state.update();
                 state.render();
-                state.window.request_redraw();
             }
             _ => {}
         }
     }
+
+    /// Requests the next frame once the frame limiter allows it, sleeping until then.
+    fn about_to_wait(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        let Some(state) = self.state.as_mut() else {
+            return;
+        };
+        match state.frame_limiter.next_frame() {
+            Some(next_frame) if Instant::now() < next_frame => {
+                event_loop.set_control_flow(ControlFlow::WaitUntil(next_frame));
+            }
+            _ => {
+                event_loop.set_control_flow(ControlFlow::Poll);
+                state.window.request_redraw();
+            }
+        }
+    }
+
+    fn device_event(
+        &mut self,
+        _event_loop: &winit::event_loop::ActiveEventLoop,
+        _device_id: winit::event::DeviceId,
+        event: DeviceEvent,
+    ) {
+        let Some(state) = self.state.as_mut() else {
+            return;
+        };
+        // Raw motion keeps working when the cursor is locked in place
+        if let DeviceEvent::MouseMotion { delta: (dx, dy) } = event
+            && state.cursor_grabbed
+        {
+            state.camera_controller.process_mouse(dx, dy);
+        }
+    }
 }
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use crate::direction::Direction;
+
+pub type BlockId = u16;
+
+/// Always registered first, so it is the id of every empty block.
+pub const AIR: BlockId = 0;
+
+/// Atlas tile coordinates written into `FaceData::u`/`FaceData::v`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextureCoord {
+    pub u: u8,
+    pub v: u8,
+}
+
+impl TextureCoord {
+    pub const fn new(u: u8, v: u8) -> Self {
+        Self { u, v }
+    }
+}
+
+/// Texture of every face of a block, indexed in `Direction::ALL` order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockTextures([TextureCoord; 6]);
+
+impl BlockTextures {
+    pub const fn new(textures: [TextureCoord; 6]) -> Self {
+        Self(textures)
+    }
+
+    pub fn get(&self, direction: Direction) -> TextureCoord {
+        self.0[direction as usize]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub name: String,
+    /// Hides the faces of neighbouring blocks.
+    pub opaque: bool,
+    /// Has see-through texels, so its faces are blended over what is behind them after the
+    /// opaque faces are drawn. Faces between two blocks of this kind are culled.
+    pub transparent: bool,
+    /// Block light level given off, up to `MAX_LIGHT`.
+    pub emission: u8,
+    pub textures: BlockTextures,
+}
+
+impl Block {
+    pub fn new(name: &str, textures: BlockTextures) -> Self {
+        Self {
+            name: name.to_owned(),
+            opaque: true,
+            transparent: false,
+            emission: 0,
+            textures,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockRegistry {
+    blocks: Vec<Block>,
+    ids: HashMap<String, BlockId>,
+}
+
+impl Default for BlockRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            blocks: Vec::new(),
+            ids: HashMap::new(),
+        };
+        registry.register(Block {
+            name: "air".to_owned(),
+            opaque: false,
+            transparent: true,
+            emission: 0,
+            textures: BlockTextures::default(),
+        });
+        registry
+    }
+}
+
+impl BlockRegistry {
+    /// Adds a block and returns its id, replacing any block already registered under its name.
+    pub fn register(&mut self, block: Block) -> BlockId {
+        if let Some(&id) = self.ids.get(&block.name) {
+            self.blocks[id as usize] = block;
+            return id;
+        }
+
+        let id = BlockId::try_from(self.blocks.len()).expect("too many blocks registered");
+        self.ids.insert(block.name.clone(), id);
+        self.blocks.push(block);
+        id
+    }
+
+    pub fn id(&self, name: &str) -> Option<BlockId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn get(&self, id: BlockId) -> &Block {
+        &self.blocks[id as usize]
+    }
+
+    /// All blocks, in id order.
+    pub fn iter(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.iter()
+    }
+}
+
+#[cfg(test)]
+pub mod testing {
+    use super::*;
+
+    /// Blocks of the registry returned by `registry`.
+    #[derive(Debug, Clone, Copy)]
+    pub struct TestBlocks {
+        /// Opaque, with a different texture on each side.
+        pub stone: BlockId,
+        /// Opaque, with textures of its own.
+        pub dirt: BlockId,
+        /// Transparent.
+        pub glass: BlockId,
+        /// Opaque, giving off block light 14.
+        pub lamp: BlockId,
+    }
+
+    /// A few blocks for the tests of the modules built on blocks.
+    pub fn registry() -> (BlockRegistry, TestBlocks) {
+        let mut registry = BlockRegistry::default();
+        let textures = |v| BlockTextures(std::array::from_fn(|n| TextureCoord::new(n as u8, v)));
+        let stone = registry.register(Block::new("stone", textures(0)));
+        let dirt = registry.register(Block::new("dirt", textures(1)));
+        let mut glass = Block::new("glass", textures(2));
+        glass.opaque = false;
+        glass.transparent = true;
+        let glass = registry.register(glass);
+        let mut lamp = Block::new("lamp", textures(3));
+        lamp.emission = 14;
+        let lamp = registry.register(lamp);
+        (
+            registry,
+            TestBlocks {
+                stone,
+                dirt,
+                glass,
+                lamp,
+            },
+        )
+    }
+}
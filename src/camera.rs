@@ -0,0 +1,325 @@
+use std::{
+    f32::consts::{FRAC_PI_2, FRAC_PI_4},
+    num::NonZero,
+};
+
+use glam::{Mat4, Vec3};
+use winit::{
+    event::{ElementState, MouseScrollDelta},
+    keyboard::KeyCode,
+};
+
+/// Keeps the view from flipping over when looking straight up or down.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+/// Pitch of the isometric view, along the diagonal of a block.
+const ISOMETRIC_PITCH: f32 = -0.615_479_7; // -atan(1 / sqrt(2))
+/// Pixels of trackpad scrolling that count as one line of a mouse wheel.
+const PIXELS_PER_LINE: f64 = 40.0;
+/// Zoom factor per line scrolled.
+const ZOOM_STEP: f32 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Projection {
+    Perspective,
+    /// Parallel projection, for top-down and isometric views.
+    Orthographic,
+}
+
+/// A camera looking along `yaw` and `pitch` in radians. A yaw of zero looks along +X and
+/// increases towards +Z.
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub projection: Projection,
+    /// Vertical field of view in radians, when perspective.
+    pub fov_y: f32,
+    /// Blocks visible from the bottom to the top of the view, when orthographic.
+    pub ortho_height: f32,
+    pub aspect: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Camera {
+    pub fn new(position: Vec3, yaw: f32, pitch: f32, aspect: f32) -> Self {
+        Self {
+            position,
+            yaw,
+            pitch,
+            projection: Projection::Perspective,
+            fov_y: 70f32.to_radians(),
+            ortho_height: 32.0,
+            aspect,
+            near: 0.1,
+            far: 1000.0,
+        }
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        let (yaw_sin, yaw_cos) = self.yaw.sin_cos();
+        let (pitch_sin, pitch_cos) = self.pitch.sin_cos();
+        Vec3::new(yaw_cos * pitch_cos, pitch_sin, yaw_sin * pitch_cos)
+    }
+
+    /// Horizontal right of the view, ignoring pitch.
+    pub fn right(&self) -> Vec3 {
+        let (yaw_sin, yaw_cos) = self.yaw.sin_cos();
+        Vec3::new(-yaw_sin, 0.0, yaw_cos)
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_to_rh(self.position, self.forward(), Vec3::Y)
+    }
+
+    pub fn projection_matrix(&self) -> Mat4 {
+        match self.projection {
+            Projection::Perspective => {
+                Mat4::perspective_rh(self.fov_y, self.aspect, self.near, self.far)
+            }
+            Projection::Orthographic => {
+                let top = self.ortho_height / 2.0;
+                let right = top * self.aspect;
+                Mat4::orthographic_rh(-right, right, -top, top, self.near, self.far)
+            }
+        }
+    }
+
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection_matrix() * self.view()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Moves freely, looking around with the mouse.
+    Fly,
+    /// Turns around `CameraController::target`, which the movement keys pan.
+    Orbit,
+    /// Looks from the eyes of a `Player`, which the movement keys walk and Space jumps.
+    Walk,
+}
+
+/// WASD, mouse and scroll wheel controls, applied to a `Camera` once per frame.
+#[derive(Debug)]
+pub struct CameraController {
+    pub mode: CameraMode,
+    /// Point the orbit camera turns around.
+    pub target: Vec3,
+    /// Distance of the orbit camera from `target`.
+    pub distance: f32,
+    /// Blocks per second.
+    pub speed: f32,
+    /// Radians per pixel of mouse motion.
+    pub sensitivity: f32,
+    forward: bool,
+    back: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    /// Mouse motion accumulated since the last update.
+    rotation: (f64, f64),
+    /// Lines scrolled since the last update, positive to zoom in.
+    zoom: f64,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        Self {
+            mode: CameraMode::Fly,
+            target: Vec3::ZERO,
+            distance: 16.0,
+            speed: 8.0,
+            sensitivity: 0.003,
+            forward: false,
+            back: false,
+            left: false,
+            right: false,
+            up: false,
+            down: false,
+            rotation: (0.0, 0.0),
+            zoom: 0.0,
+        }
+    }
+}
+
+impl CameraController {
+    /// Tracks the movement keys, ignoring any other key.
+    pub fn process_keyboard(&mut self, key: KeyCode, state: ElementState) {
+        let pressed = state == ElementState::Pressed;
+        match key {
+            KeyCode::KeyW => self.forward = pressed,
+            KeyCode::KeyS => self.back = pressed,
+            KeyCode::KeyA => self.left = pressed,
+            KeyCode::KeyD => self.right = pressed,
+            KeyCode::Space => self.up = pressed,
+            KeyCode::ShiftLeft => self.down = pressed,
+            _ => {}
+        }
+    }
+
+    pub fn process_mouse(&mut self, dx: f64, dy: f64) {
+        self.rotation.0 += dx;
+        self.rotation.1 += dy;
+    }
+
+    pub fn process_scroll(&mut self, delta: MouseScrollDelta) {
+        self.zoom += match delta {
+            MouseScrollDelta::LineDelta(_, y) => y as f64,
+            MouseScrollDelta::PixelDelta(position) => position.y / PIXELS_PER_LINE,
+        };
+    }
+
+    /// Releases every key, e.g. when the window loses focus and key releases go missing.
+    pub fn reset(&mut self) {
+        *self = Self {
+            mode: self.mode,
+            target: self.target,
+            distance: self.distance,
+            speed: self.speed,
+            sensitivity: self.sensitivity,
+            ..Default::default()
+        };
+    }
+
+    /// Switches to `mode`, keeping the view where it is. The orbit target becomes the centre of
+    /// the block `distance` ahead of the camera.
+    pub fn set_mode(&mut self, camera: &Camera, mode: CameraMode) {
+        if mode == CameraMode::Orbit && self.mode != CameraMode::Orbit {
+            self.target = (camera.position + camera.forward() * self.distance).floor() + 0.5;
+        }
+        self.mode = mode;
+    }
+
+    /// Orbits an orthographic `camera` at the isometric angle nearest its current yaw.
+    pub fn set_isometric(&mut self, camera: &mut Camera) {
+        self.set_mode(camera, CameraMode::Orbit);
+        camera.projection = Projection::Orthographic;
+        camera.yaw = ((camera.yaw - FRAC_PI_4) / FRAC_PI_2).round() * FRAC_PI_2 + FRAC_PI_4;
+        camera.pitch = ISOMETRIC_PITCH;
+    }
+
+    /// Moves and turns `camera` by the input since the last update, `dt` seconds ago.
+    pub fn update(&mut self, camera: &mut Camera, dt: f32) {
+        let (dx, dy) = std::mem::take(&mut self.rotation);
+        camera.yaw = (camera.yaw + dx as f32 * self.sensitivity) % std::f32::consts::TAU;
+        camera.pitch = (camera.pitch - dy as f32 * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+
+        let zoom = ZOOM_STEP.powf(std::mem::take(&mut self.zoom) as f32);
+        if camera.projection == Projection::Orthographic {
+            camera.ortho_height = (camera.ortho_height * zoom).clamp(2.0, 512.0);
+        }
+
+        let direction = self.walk_direction(camera) + Vec3::Y * axis(self.up, self.down);
+        let movement = direction.normalize_or_zero() * self.speed * dt;
+
+        match self.mode {
+            CameraMode::Fly => camera.position += movement,
+            // Moved by the player instead
+            CameraMode::Walk => {}
+            CameraMode::Orbit => {
+                self.target += movement;
+                if camera.projection == Projection::Perspective {
+                    self.distance = (self.distance * zoom).clamp(1.0, 512.0);
+                }
+                camera.position = self.target - camera.forward() * self.distance;
+            }
+        }
+    }
+
+    /// Horizontal direction of the held movement keys relative to where `camera` faces, not
+    /// normalized.
+    pub fn walk_direction(&self, camera: &Camera) -> Vec3 {
+        let horizontal_forward = Vec3::new(camera.yaw.cos(), 0.0, camera.yaw.sin());
+        horizontal_forward * axis(self.forward, self.back)
+            + camera.right() * axis(self.right, self.left)
+    }
+
+    /// Whether the jump key, the same as fly up, is held.
+    pub fn jumping(&self) -> bool {
+        self.up
+    }
+}
+
+/// 1 if only `positive` is held, -1 if only `negative` is, otherwise 0.
+fn axis(positive: bool, negative: bool) -> f32 {
+    positive as i32 as f32 - negative as i32 as f32
+}
+
+/// Camera data laid out as `Camera` in `shader.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct CameraUniform {
+    view_projection: [[f32; 4]; 4],
+    inverse_view_projection: [[f32; 4]; 4],
+    position: [f32; 4],
+}
+
+/// The uniform buffer and bind group exposing a `Camera` to the render pipelines.
+#[derive(Debug)]
+pub struct CameraBinding {
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl CameraBinding {
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Camera Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZero::new(std::mem::size_of::<CameraUniform>() as u64),
+                },
+                count: None,
+            }],
+        })
+    }
+
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Camera Buffer"),
+            size: std::mem::size_of::<CameraUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Camera Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self { buffer, bind_group }
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, camera: &Camera) {
+        self.write(queue, camera.view_projection(), camera.position);
+    }
+
+    /// Uploads a view projection matrix that doesn't come from a `Camera`, for passes that
+    /// don't need the camera position.
+    pub fn update_view_projection(&self, queue: &wgpu::Queue, view_projection: Mat4) {
+        self.write(queue, view_projection, Vec3::ZERO);
+    }
+
+    fn write(&self, queue: &wgpu::Queue, view_projection: Mat4, position: Vec3) {
+        let uniform = CameraUniform {
+            view_projection: view_projection.to_cols_array_2d(),
+            inverse_view_projection: view_projection.inverse().to_cols_array_2d(),
+            position: position.extend(0.0).into(),
+        };
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+}
@@ -0,0 +1,565 @@
+use crate::{
+    block::{AIR, BlockId, BlockRegistry, TextureCoord},
+    data::FaceData,
+    direction::Direction,
+    lightmap::Light,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshMode {
+    /// One face per visible block side.
+    Naive,
+    /// Coplanar faces with the same texture, light and uniform occlusion are merged into larger
+    /// rectangles.
+    Greedy,
+}
+
+/// Edge length of a chunk, matching the 4-bit coordinates packed into `FaceData`.
+pub const CHUNK_SIZE: usize = 16;
+pub const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+/// Face lists of a chunk: the opaque faces of each `Direction` in `Direction::ALL` order, then
+/// the transparent faces of each direction.
+pub const FACE_LISTS: usize = 12;
+/// Faces of a chunk, one list per `FACE_LISTS` entry.
+pub type FaceLists = [Vec<FaceData>; FACE_LISTS];
+
+/// Index of the face list holding the faces of a block in `direction`.
+fn face_list(direction: Direction, transparent: bool) -> usize {
+    direction as usize + transparent as usize * Direction::ALL.len()
+}
+
+/// The 3x3x3 chunks centred on a chunk, indexed as laid out by `neighbour_offset`, or `None`
+/// where no chunk is loaded. The centre entry is ignored.
+pub type Neighbours<'a> = [Option<&'a Chunk>; 27];
+
+/// Chunk offset, each axis in -1..=1, of an index into `Neighbours`.
+pub fn neighbour_offset(index: usize) -> [i32; 3] {
+    let index = index as i32;
+    [index % 3 - 1, index / 3 % 3 - 1, index / 9 - 1]
+}
+
+fn neighbour_index([dx, dy, dz]: [i32; 3]) -> usize {
+    ((dx + 1) + (dy + 1) * 3 + (dz + 1) * 9) as usize
+}
+
+/// A visible block face before it is packed into `FaceData`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Face {
+    texture: TextureCoord,
+    /// As in `FaceData`, two bits per corner.
+    ao: u8,
+    /// Light of the block the face looks into.
+    light: Light,
+    /// Blended over the faces behind it instead of hiding them.
+    transparent: bool,
+}
+
+impl Face {
+    /// Whether every corner is equally occluded, so the face can be merged with its neighbours
+    /// without stretching the occlusion gradient across them.
+    fn uniform_ao(&self) -> bool {
+        self.ao == (self.ao & 3) * 0b0101_0101
+    }
+
+    fn data(&self, [x, y, z]: [usize; 3]) -> FaceData {
+        FaceData::new(x as u8, y as u8, z as u8, self.texture.u, self.texture.v)
+            .with_ao(self.ao)
+            .with_light(self.light)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    blocks: Box<[BlockId; CHUNK_VOLUME]>,
+    /// Kept up to date by `lightmap`.
+    light: Box<[Light; CHUNK_VOLUME]>,
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self {
+            blocks: Box::new([AIR; CHUNK_VOLUME]),
+            light: Box::new([Light::default(); CHUNK_VOLUME]),
+        }
+    }
+}
+
+impl Chunk {
+    fn index(x: usize, y: usize, z: usize) -> usize {
+        x + y * CHUNK_SIZE + z * CHUNK_SIZE * CHUNK_SIZE
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> BlockId {
+        self.blocks[Self::index(x, y, z)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, block: BlockId) {
+        self.blocks[Self::index(x, y, z)] = block;
+    }
+
+    pub fn light(&self, x: usize, y: usize, z: usize) -> Light {
+        self.light[Self::index(x, y, z)]
+    }
+
+    pub fn set_light(&mut self, x: usize, y: usize, z: usize, light: Light) {
+        self.light[Self::index(x, y, z)] = light;
+    }
+
+    /// Darkens every block.
+    pub fn clear_light(&mut self) {
+        self.light.fill(Light::default());
+    }
+
+    /// Number of blocks that aren't air.
+    pub fn block_count(&self) -> usize {
+        self.blocks.iter().filter(|&&block| block != AIR).count()
+    }
+
+    /// The chunk containing a position relative to this chunk, which may lie at most one block
+    /// outside of it on every axis, and the position within that chunk. Positions outside are
+    /// looked up in the neighbour on that side, if it is loaded.
+    fn relative<'a>(
+        &'a self,
+        neighbours: &Neighbours<'a>,
+        x: i32,
+        y: i32,
+        z: i32,
+    ) -> Option<(&'a Chunk, [usize; 3])> {
+        let size = CHUNK_SIZE as i32;
+        let offset = [x, y, z].map(|n| n.div_euclid(size));
+        let chunk = if offset == [0; 3] {
+            Some(self)
+        } else {
+            neighbours[neighbour_index(offset)]
+        };
+        chunk.map(|chunk| (chunk, [x, y, z].map(|n| n.rem_euclid(size) as usize)))
+    }
+
+    /// Block at a position relative to this chunk as in `relative`, air if it is not loaded.
+    fn get_relative(&self, neighbours: &Neighbours, x: i32, y: i32, z: i32) -> BlockId {
+        self.relative(neighbours, x, y, z)
+            .map(|(chunk, [x, y, z])| chunk.get(x, y, z))
+            .unwrap_or(AIR)
+    }
+
+    /// Light at a position relative to this chunk as in `relative`, open sky if it is not
+    /// loaded.
+    fn light_relative(&self, neighbours: &Neighbours, x: i32, y: i32, z: i32) -> Light {
+        self.relative(neighbours, x, y, z)
+            .map(|(chunk, [x, y, z])| chunk.light(x, y, z))
+            .unwrap_or(Light::SKY)
+    }
+
+    /// The face of the block at (x, y, z) pointing in `direction`, or `None` if that face is
+    /// culled. Faces touching an opaque neighbour, or a transparent neighbour of the same block,
+    /// are culled.
+    fn visible_face(
+        &self,
+        registry: &BlockRegistry,
+        neighbours: &Neighbours,
+        x: usize,
+        y: usize,
+        z: usize,
+        direction: Direction,
+    ) -> Option<Face> {
+        let id = self.get(x, y, z);
+        if id == AIR {
+            return None;
+        }
+        let block = registry.get(id);
+
+        let [dx, dy, dz] = direction.offset();
+        let [nx, ny, nz] = [x as i32 + dx, y as i32 + dy, z as i32 + dz];
+        let neighbour_id = self.get_relative(neighbours, nx, ny, nz);
+        let neighbour = registry.get(neighbour_id);
+
+        if neighbour.opaque || (block.transparent && neighbour_id == id) {
+            return None;
+        }
+
+        Some(Face {
+            texture: block.textures.get(direction),
+            ao: self.ambient_occlusion(registry, neighbours, [nx, ny, nz], direction),
+            light: self.light_relative(neighbours, nx, ny, nz),
+            transparent: block.transparent,
+        })
+    }
+
+    /// Occlusion of each corner of a face by the opaque blocks around `front`, the block the
+    /// face looks into. A corner is darkened by the two blocks beside it and the one diagonal
+    /// to it, and fully dark if both blocks beside it are opaque.
+    fn ambient_occlusion(
+        &self,
+        registry: &BlockRegistry,
+        neighbours: &Neighbours,
+        front: [i32; 3],
+        direction: Direction,
+    ) -> u8 {
+        let normal = direction.offset();
+        let occludes = |offset: [i32; 3]| {
+            let [x, y, z] = [0, 1, 2].map(|axis| front[axis] + offset[axis]);
+            registry.get(self.get_relative(neighbours, x, y, z)).opaque
+        };
+
+        let mut ao = 0;
+        for (n, corner) in direction.corners().into_iter().enumerate() {
+            // Steps from the middle of the face towards the corner along each face axis
+            let step = [0, 1, 2].map(|axis| (corner[axis] * 2 - 1) * (1 - normal[axis].abs()));
+            let mut sides = [[0; 3]; 2];
+            for (side, axis) in sides
+                .iter_mut()
+                .zip((0..3).filter(|&axis| normal[axis] == 0))
+            {
+                side[axis] = step[axis];
+            }
+
+            let (side1, side2, diagonal) = (occludes(sides[0]), occludes(sides[1]), occludes(step));
+            let value = if side1 && side2 {
+                0
+            } else {
+                3 - side1 as u8 - side2 as u8 - diagonal as u8
+            };
+            ao |= value << (2 * n);
+        }
+        ao
+    }
+
+    /// Generates the visible faces of this chunk, split into the lists of `FaceLists`.
+    pub fn face_data(
+        &self,
+        registry: &BlockRegistry,
+        neighbours: &Neighbours,
+        mode: MeshMode,
+    ) -> FaceLists {
+        match mode {
+            MeshMode::Naive => self.naive_face_data(registry, neighbours),
+            MeshMode::Greedy => self.greedy_face_data(registry, neighbours),
+        }
+    }
+
+    fn naive_face_data(&self, registry: &BlockRegistry, neighbours: &Neighbours) -> FaceLists {
+        let mut face_data = FaceLists::default();
+
+        for z in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    for direction in Direction::ALL {
+                        if let Some(face) =
+                            self.visible_face(registry, neighbours, x, y, z, direction)
+                        {
+                            face_data[face_list(direction, face.transparent)]
+                                .push(face.data([x, y, z]));
+                        }
+                    }
+                }
+            }
+        }
+
+        face_data
+    }
+
+    /// Merges adjacent faces with the same texture, light and uniform occlusion into rectangles,
+    /// slice by slice.
+    fn greedy_face_data(&self, registry: &BlockRegistry, neighbours: &Neighbours) -> FaceLists {
+        let mut face_data = FaceLists::default();
+
+        for direction in Direction::ALL {
+            for depth in 0..CHUNK_SIZE {
+                // Indexed by [height][width] along the face axes of `direction`
+                let mut mask = [[None; CHUNK_SIZE]; CHUNK_SIZE];
+                for (h, row) in mask.iter_mut().enumerate() {
+                    for (w, cell) in row.iter_mut().enumerate() {
+                        let [x, y, z] = slice_position(direction, depth, w, h);
+                        *cell = self.visible_face(registry, neighbours, x, y, z, direction);
+                    }
+                }
+
+                for h in 0..CHUNK_SIZE {
+                    let mut w = 0;
+                    while w < CHUNK_SIZE {
+                        let Some(face) = mask[h][w] else {
+                            w += 1;
+                            continue;
+                        };
+
+                        let (width, height) = if face.uniform_ao() {
+                            let width = mask[h][w..]
+                                .iter()
+                                .take_while(|&&cell| cell == Some(face))
+                                .count();
+                            let height = mask[h..]
+                                .iter()
+                                .take_while(|row| {
+                                    row[w..w + width].iter().all(|&cell| cell == Some(face))
+                                })
+                                .count();
+                            (width, height)
+                        } else {
+                            (1, 1)
+                        };
+
+                        for row in &mut mask[h..h + height] {
+                            row[w..w + width].fill(None);
+                        }
+
+                        let position = slice_position(direction, depth, w, h);
+                        face_data[face_list(direction, face.transparent)]
+                            .push(face.data(position).with_size(width as u8, height as u8));
+
+                        w += width;
+                    }
+                }
+            }
+        }
+
+        face_data
+    }
+}
+
+/// Position of the block at `depth` along the normal of `direction`, and at (`w`, `h`) along
+/// the width and height axes that `geometry.wgsl` uses to size faces in that direction.
+fn slice_position(direction: Direction, depth: usize, w: usize, h: usize) -> [usize; 3] {
+    match direction {
+        Direction::UP | Direction::DOWN => [w, depth, h],
+        Direction::LEFT | Direction::RIGHT => [depth, h, w],
+        Direction::FRONT | Direction::BACK => [w, h, depth],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        block::testing::{self, TestBlocks},
+        direction::{FLIPPED_QUAD_CORNERS, QUAD_CORNERS},
+    };
+
+    /// Every block side covered by a face, keyed by face list and block position, with the
+    /// texture, occlusion and light of the face covering it. Panics if two faces overlap.
+    fn coverage(face_data: &FaceLists) -> HashMap<(usize, [usize; 3]), [u8; 5]> {
+        let mut covered = HashMap::new();
+        for (list, faces) in face_data.iter().enumerate() {
+            let direction = Direction::ALL[list % Direction::ALL.len()];
+            for face in faces {
+                let face = face.decode();
+                let position = [face.x, face.y, face.z].map(usize::from);
+                let (depth, w, h) = match direction {
+                    Direction::UP | Direction::DOWN => (position[1], position[0], position[2]),
+                    Direction::LEFT | Direction::RIGHT => (position[0], position[2], position[1]),
+                    Direction::FRONT | Direction::BACK => (position[2], position[0], position[1]),
+                };
+                for dh in 0..face.height as usize {
+                    for dw in 0..face.width as usize {
+                        let block = slice_position(direction, depth, w + dw, h + dh);
+                        let value = [face.u, face.v, face.ao, face.sky_light, face.block_light];
+                        assert!(
+                            covered.insert((list, block), value).is_none(),
+                            "{block:?} is covered twice in list {list}"
+                        );
+                    }
+                }
+            }
+        }
+        covered
+    }
+
+    fn face_counts(face_data: &FaceLists) -> Vec<usize> {
+        face_data.iter().map(Vec::len).collect()
+    }
+
+    /// A few blocks scattered with a fixed seed, mixing stone, dirt, glass and air.
+    fn scattered(blocks: &TestBlocks) -> Chunk {
+        let mut chunk = Chunk::default();
+        let mut seed = 0x2545_f491_u32;
+        for z in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 17;
+                    seed ^= seed << 5;
+                    // Mostly stone low down and mostly air up high, so both kinds of faces merge
+                    let block = match (seed % 8, y < 8) {
+                        (0, _) => blocks.glass,
+                        (1, _) => blocks.dirt,
+                        (2..=5, true) | (2, false) => blocks.stone,
+                        _ => AIR,
+                    };
+                    chunk.set(x, y, z, block);
+                }
+            }
+        }
+        chunk
+    }
+
+    #[test]
+    fn single_block_has_six_faces() {
+        let (registry, TestBlocks { stone, .. }) = testing::registry();
+        let mut chunk = Chunk::default();
+        chunk.set(3, 4, 5, stone);
+
+        for mode in [MeshMode::Naive, MeshMode::Greedy] {
+            let face_data = chunk.face_data(&registry, &[None; 27], mode);
+            assert_eq!(
+                face_counts(&face_data),
+                [1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0]
+            );
+            for faces in &face_data[..Direction::ALL.len()] {
+                let face = faces[0].decode();
+                assert_eq!([face.x, face.y, face.z], [3, 4, 5]);
+                assert_eq!([face.width, face.height], [1, 1]);
+            }
+        }
+    }
+
+    #[test]
+    fn greedy_merges_a_slab_into_one_face_per_side() {
+        let (registry, TestBlocks { stone, .. }) = testing::registry();
+        let mut chunk = Chunk::default();
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                chunk.set(x, 0, z, stone);
+            }
+        }
+
+        let naive = chunk.face_data(&registry, &[None; 27], MeshMode::Naive);
+        let greedy = chunk.face_data(&registry, &[None; 27], MeshMode::Greedy);
+        assert_eq!(
+            face_counts(&naive),
+            [256, 256, 16, 16, 16, 16, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(face_counts(&greedy), [1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(coverage(&greedy), coverage(&naive));
+    }
+
+    #[test]
+    fn greedy_covers_the_same_faces_as_naive() {
+        let (registry, blocks) = testing::registry();
+        let chunk = scattered(&blocks);
+
+        let naive = chunk.face_data(&registry, &[None; 27], MeshMode::Naive);
+        let greedy = chunk.face_data(&registry, &[None; 27], MeshMode::Greedy);
+        let naive_count: usize = face_counts(&naive).iter().sum();
+        let greedy_count: usize = face_counts(&greedy).iter().sum();
+        assert!(
+            greedy_count < naive_count,
+            "{greedy_count} >= {naive_count}"
+        );
+        assert_eq!(coverage(&greedy), coverage(&naive));
+    }
+
+    #[test]
+    fn greedy_covers_the_same_faces_as_naive_next_to_neighbours() {
+        let (registry, blocks) = testing::registry();
+        let chunk = scattered(&blocks);
+        let mut above = Chunk::default();
+        let mut beside = Chunk::default();
+        let kinds = [blocks.stone, blocks.dirt, blocks.glass];
+        for a in 0..CHUNK_SIZE {
+            for b in 0..CHUNK_SIZE {
+                above.set(a, 0, b, kinds[(a + b) % 3]);
+                beside.set(CHUNK_SIZE - 1, a, b, blocks.stone);
+            }
+        }
+        let mut neighbours: Neighbours = [None; 27];
+        neighbours[neighbour_index([0, 1, 0])] = Some(&above);
+        neighbours[neighbour_index([-1, 0, 0])] = Some(&beside);
+
+        let naive = chunk.face_data(&registry, &neighbours, MeshMode::Naive);
+        let greedy = chunk.face_data(&registry, &neighbours, MeshMode::Greedy);
+        assert_eq!(coverage(&greedy), coverage(&naive));
+    }
+
+    #[test]
+    fn faces_between_transparent_blocks_of_one_kind_are_culled() {
+        let (registry, TestBlocks { stone, glass, .. }) = testing::registry();
+        let mut chunk = Chunk::default();
+        chunk.set(0, 0, 0, glass);
+        chunk.set(1, 0, 0, glass);
+        chunk.set(0, 1, 0, stone);
+
+        let face_data = chunk.face_data(&registry, &[None; 27], MeshMode::Naive);
+        // The glass below hides nothing of the stone, but the stone hides the glass top
+        assert_eq!(
+            face_counts(&face_data),
+            [1, 1, 1, 1, 1, 1, 1, 2, 1, 1, 2, 2]
+        );
+    }
+
+    /// Occlusion of each corner of the top of a stone block at (8, 8, 8), with more stone at
+    /// `around`.
+    fn top_ao(around: &[[usize; 3]]) -> [u8; 4] {
+        let (registry, TestBlocks { stone, .. }) = testing::registry();
+        let mut chunk = Chunk::default();
+        chunk.set(8, 8, 8, stone);
+        for &[x, y, z] in around {
+            chunk.set(x, y, z, stone);
+        }
+
+        let face_data = chunk.face_data(&registry, &[None; 27], MeshMode::Naive);
+        let face = face_data[face_list(Direction::UP, false)]
+            .iter()
+            .map(FaceData::decode)
+            .find(|face| [face.x, face.y, face.z] == [8, 8, 8])
+            .expect("nothing is on top of the block");
+        std::array::from_fn(|n| face.ao >> (2 * n) & 3)
+    }
+
+    /// Vertex corners of a face with the corner occlusion `ao`, split like `quad_corner` in
+    /// face.wgsl.
+    fn quad(ao: [u8; 4]) -> [usize; 6] {
+        if ao[0] + ao[3] > ao[1] + ao[2] {
+            FLIPPED_QUAD_CORNERS
+        } else {
+            QUAD_CORNERS
+        }
+    }
+
+    #[test]
+    fn blocks_beside_a_face_darken_its_corners() {
+        assert_eq!(top_ao(&[]), [3, 3, 3, 3]);
+        // Blocks below the face and level with it don't shade it
+        assert_eq!(top_ao(&[[9, 8, 8], [8, 7, 8]]), [3, 3, 3, 3]);
+
+        // Along the edge of the top at x = 9, corners 2 and 3
+        assert_eq!(top_ao(&[[9, 9, 8]]), [3, 3, 2, 2]);
+        // Diagonal to corner 0 only
+        assert_eq!(top_ao(&[[7, 9, 7]]), [2, 3, 3, 3]);
+        assert_eq!(top_ao(&[[9, 9, 8], [7, 9, 7]]), [2, 3, 2, 2]);
+        // A side and the diagonal next to it
+        assert_eq!(top_ao(&[[9, 9, 8], [9, 9, 9]]), [3, 3, 2, 1]);
+    }
+
+    #[test]
+    fn corners_between_two_blocks_are_fully_dark() {
+        // Both sides of corner 3 and one side of each of corners 1 and 2
+        assert_eq!(top_ao(&[[9, 9, 8], [8, 9, 9]]), [3, 2, 2, 0]);
+        // However dark its diagonal is
+        assert_eq!(top_ao(&[[9, 9, 8], [8, 9, 9], [9, 9, 9]]), [3, 2, 2, 0]);
+    }
+
+    #[test]
+    fn quads_split_along_their_brighter_diagonal() {
+        assert_eq!(quad(top_ao(&[])), QUAD_CORNERS);
+
+        // Each corner darkened on its own by the block diagonal to it
+        for (n, diagonal) in [[7, 9, 7], [7, 9, 9], [9, 9, 7], [9, 9, 9]]
+            .into_iter()
+            .enumerate()
+        {
+            let ao = top_ao(&[diagonal]);
+            let mut expected = [3; 4];
+            expected[n] = 2;
+            assert_eq!(ao, expected);
+
+            // Only the triangle holding the dark corner is shaded
+            let corners = quad(ao);
+            let flipped = n == 1 || n == 2;
+            assert_eq!(corners == FLIPPED_QUAD_CORNERS, flipped, "corner {n}");
+            let triangles = corners.chunks(3).filter(|triangle| triangle.contains(&n));
+            assert_eq!(triangles.count(), 1, "corner {n}");
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use std::num::NonZero;
+
+use crate::{
+    block::BlockRegistry,
+    chunk::{CHUNK_SIZE, FACE_LISTS},
+    direction::Direction,
+    memory::{Allocation, GpuMemory},
+    render::{FaceGroup, create_shader_module},
+    world::{ChunkPos, World},
+};
+
+/// Edge length of the voxel volume uploaded per chunk, including a one block border taken from
+/// the neighbouring chunks.
+const PADDED_CHUNK_SIZE: usize = CHUNK_SIZE + 2;
+const PADDED_CHUNK_VOLUME: usize = PADDED_CHUNK_SIZE * PADDED_CHUNK_SIZE * PADDED_CHUNK_SIZE;
+
+const WORKGROUP_SIZE: u32 = 4;
+
+const FLAG_OPAQUE: u32 = 1;
+const FLAG_TRANSPARENT: u32 = 1 << 1;
+
+/// Culls chunk faces on the GPU, writing them straight into the buffers of a `FaceGroup`.
+#[derive(Debug)]
+pub struct CullPipeline {
+    bind_group_layout: wgpu::BindGroupLayout,
+    count_pipeline: wgpu::ComputePipeline,
+    offsets_pipeline: wgpu::ComputePipeline,
+    emit_pipeline: wgpu::ComputePipeline,
+    block_info_buffer: wgpu::Buffer,
+}
+
+impl CullPipeline {
+    pub fn new(device: &wgpu::Device, registry: &BlockRegistry) -> Self {
+        let storage =
+            |binding, read_only, min_binding_size: Option<u64>| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only },
+                    has_dynamic_offset: false,
+                    min_binding_size: min_binding_size.and_then(NonZero::new),
+                },
+                count: None,
+            };
+
+        let u32_size = std::mem::size_of::<u32>() as u64;
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Cull Group Layout"),
+            entries: &[
+                // Voxels
+                storage(0, true, None),
+                // Block info
+                storage(1, true, None),
+                // Face counts
+                storage(2, false, Some(u32_size * FACE_LISTS as u64)),
+                // Face offsets
+                storage(3, false, Some(u32_size * (FACE_LISTS + 1) as u64)),
+                // Face data
+                storage(4, false, None),
+                // Indirect arguments
+                storage(5, false, Some(u32_size * 11)),
+            ],
+        });
+
+        let shader = create_shader_module(device, "cull.wgsl", &[include_str!("./cull.wgsl")]);
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cull Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |entry_point: &str| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Cull Pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+
+        use wgpu::util::DeviceExt;
+        let block_info_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Block Info Buffer"),
+            contents: bytemuck::cast_slice(&Self::block_info(registry)),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        Self {
+            count_pipeline: create_pipeline("count"),
+            offsets_pipeline: create_pipeline("offsets"),
+            emit_pipeline: create_pipeline("emit"),
+            bind_group_layout,
+            block_info_buffer,
+        }
+    }
+
+    /// Packs the culling flags and face textures of every registered block, laid out as
+    /// `BlockInfo` in `cull.wgsl`.
+    fn block_info(registry: &BlockRegistry) -> Vec<[u32; 4]> {
+        registry
+            .iter()
+            .map(|block| {
+                let mut info = [0; 4];
+                if block.opaque {
+                    info[0] |= FLAG_OPAQUE;
+                }
+                if block.transparent {
+                    info[0] |= FLAG_TRANSPARENT;
+                }
+                for (n, direction) in Direction::ALL.into_iter().enumerate() {
+                    let texture = block.textures.get(direction);
+                    let packed = texture.u as u32 | (texture.v as u32) << 8;
+                    info[1 + n / 2] |= packed << (16 * (n % 2));
+                }
+                info
+            })
+            .collect()
+    }
+
+    /// Records the passes culling `cull_group`'s voxels into `face_group`.
+    pub fn cull(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        cull_group: &mut CullGroup,
+        face_group: &mut FaceGroup,
+        memory: &GpuMemory,
+    ) {
+        encoder.clear_buffer(&cull_group.counts_buffer, 0, None);
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Cull Pass"),
+            timestamp_writes: None,
+        });
+        let workgroups = CHUNK_SIZE as u32 / WORKGROUP_SIZE;
+
+        compute_pass.set_bind_group(
+            0,
+            cull_group.bind_group(device, self, face_group, memory),
+            &[],
+        );
+        compute_pass.set_pipeline(&self.count_pipeline);
+        compute_pass.dispatch_workgroups(workgroups, workgroups, workgroups);
+        compute_pass.set_pipeline(&self.offsets_pipeline);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+        compute_pass.set_pipeline(&self.emit_pipeline);
+        compute_pass.dispatch_workgroups(workgroups, workgroups, workgroups);
+
+        cull_group.dirty = false;
+        face_group.dirty = true;
+    }
+}
+
+/// Voxels of one chunk and the bindings to cull them into its `FaceGroup`.
+#[derive(Debug)]
+pub struct CullGroup {
+    voxel_buffer: wgpu::Buffer,
+    counts_buffer: wgpu::Buffer,
+    bind_group: Option<wgpu::BindGroup>,
+    /// `GpuMemory::generation` the bind group was created at.
+    generation: u64,
+    /// `FaceGroup::faces` the bind group was created for, replaced when the face group grows.
+    faces: Option<Allocation>,
+    /// Set when the voxels changed since they were last culled.
+    pub dirty: bool,
+}
+
+impl CullGroup {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let voxel_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Voxel Buffer"),
+            // One u32 per voxel: the block id, then its sky light at bit 16 and block light at bit 20
+            size: PADDED_CHUNK_VOLUME as u64 * std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let counts_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Face Count Buffer"),
+            size: (std::mem::size_of::<u32>() * FACE_LISTS) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            voxel_buffer,
+            counts_buffer,
+            bind_group: None,
+            generation: 0,
+            faces: None,
+            dirty: false,
+        }
+    }
+
+    fn bind_group(
+        &mut self,
+        device: &wgpu::Device,
+        pipeline: &CullPipeline,
+        face_group: &FaceGroup,
+        memory: &GpuMemory,
+    ) -> &wgpu::BindGroup {
+        if self.bind_group.is_none()
+            || self.generation != memory.generation()
+            || self.faces != Some(face_group.faces())
+        {
+            self.generation = memory.generation();
+            self.faces = Some(face_group.faces());
+            self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Cull Bind Group"),
+                layout: &pipeline.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: self.voxel_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: pipeline.block_info_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: self.counts_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: face_group.offsets_buffer().as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: memory.faces.binding(face_group.faces()),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: face_group.indirect_buffer().as_entire_binding(),
+                    },
+                ],
+            }));
+        }
+        self.bind_group
+            .as_ref()
+            .expect("bind group is created above")
+    }
+
+    /// Uploads the blocks and light of the chunk at `pos` and its border, to be culled on the
+    /// next frame.
+    pub fn set_voxels(&mut self, queue: &wgpu::Queue, world: &World, pos: ChunkPos) {
+        let [ox, oy, oz] = pos.origin();
+        let mut voxels = Vec::with_capacity(PADDED_CHUNK_VOLUME);
+
+        let range = -1..=CHUNK_SIZE as i32;
+        for z in range.clone() {
+            for y in range.clone() {
+                for x in range.clone() {
+                    let [x, y, z] = [ox + x, oy + y, oz + z];
+                    let light = world.light(x, y, z);
+                    voxels.push(
+                        world.get(x, y, z) as u32
+                            | (light.sky as u32) << 16
+                            | (light.block as u32) << 20,
+                    );
+                }
+            }
+        }
+
+        queue.write_buffer(&self.voxel_buffer, 0, bytemuck::cast_slice(&voxels));
+        self.dirty = true;
+    }
+}
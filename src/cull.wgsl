@@ -0,0 +1,204 @@
+
+const CHUNK_SIZE: i32 = 16;
+const PADDED_CHUNK_SIZE: i32 = 18;
+
+// Matches `GEOMETRY_WORKGROUP_SIZE` in render.rs
+const GEOMETRY_WORKGROUP_SIZE: u32 = 64;
+
+// Matches `FACE_LISTS` in chunk.rs: the opaque faces of each direction, then the transparent
+// faces of each direction
+const FACE_LISTS: u32 = 12;
+
+const AIR: u32 = 0;
+const FLAG_OPAQUE: u32 = 1;
+const FLAG_TRANSPARENT: u32 = 2;
+
+struct BlockInfo {
+    flags: u32,
+    // Two u8 x u8 texture coordinates per u32, in face direction order
+    textures: array<u32, 3>,
+}
+
+struct IndirectArgs {
+    // Geometry pass dispatch
+    workgroups_x: u32,
+    workgroups_y: u32,
+    workgroups_z: u32,
+    // Draw of the opaque part of the geometry pass output
+    vertex_count: u32,
+    instance_count: u32,
+    first_vertex: u32,
+    first_instance: u32,
+    // Draw of the transparent part
+    transparent_vertex_count: u32,
+    transparent_instance_count: u32,
+    transparent_first_vertex: u32,
+    transparent_first_instance: u32,
+}
+
+// Block id in the low 16 bits, then 4 bits each of sky and block light, over the chunk and a
+// one block border
+@group(0)
+@binding(0)
+var<storage> voxels: array<u32>;
+
+@group(0)
+@binding(1)
+var<storage> block_info: array<BlockInfo>;
+
+// Face counts while counting, then write cursors while emitting, indexed by `face_list`
+@group(0)
+@binding(2)
+var<storage, read_write> face_counts: array<atomic<u32>, FACE_LISTS>;
+
+// Start of each face list, followed by the total face count
+@group(0)
+@binding(3)
+var<storage, read_write> face_offsets: array<u32, 13>;
+
+@group(0)
+@binding(4)
+var<storage, read_write> face_buffer: array<vec2<u32>>;
+
+@group(0)
+@binding(5)
+var<storage, read_write> indirect_args: IndirectArgs;
+
+const neighbours: array<vec3<i32>, 6> = array(
+    vec3<i32>(0, 1, 0), // Up
+    vec3<i32>(0, -1, 0), // Down
+    vec3<i32>(-1, 0, 0), // Left
+    vec3<i32>(1, 0, 0), // Right
+    vec3<i32>(0, 0, 1), // Front
+    vec3<i32>(0, 0, -1), // Back
+);
+
+fn packed_voxel(pos: vec3<i32>) -> u32 {
+    let padded = pos + vec3<i32>(1, 1, 1);
+    return voxels[padded.x + padded.y * PADDED_CHUNK_SIZE + padded.z * PADDED_CHUNK_SIZE * PADDED_CHUNK_SIZE];
+}
+
+fn voxel(pos: vec3<i32>) -> u32 {
+    return packed_voxel(pos) & 0xffff;
+}
+
+fn sky_light(pos: vec3<i32>) -> u32 {
+    return (packed_voxel(pos) >> 16) & 0xf;
+}
+
+fn block_light(pos: vec3<i32>) -> u32 {
+    return (packed_voxel(pos) >> 20) & 0xf;
+}
+
+fn is_visible(pos: vec3<i32>, direction: u32) -> bool {
+    let block = voxel(pos);
+    if block == AIR {
+        return false;
+    }
+
+    let neighbour = voxel(pos + neighbours[direction]);
+    if (block_info[neighbour].flags & FLAG_OPAQUE) != 0 {
+        return false;
+    }
+    return (block_info[block].flags & FLAG_TRANSPARENT) == 0 || neighbour != block;
+}
+
+// Face list of the face of the block at `pos` in `direction`, as `face_list` in chunk.rs
+fn face_list(pos: vec3<i32>, direction: u32) -> u32 {
+    if (block_info[voxel(pos)].flags & FLAG_TRANSPARENT) == 0 {
+        return direction;
+    }
+    return 6 + direction;
+}
+
+fn occludes(pos: vec3<i32>) -> bool {
+    return (block_info[voxel(pos)].flags & FLAG_OPAQUE) != 0;
+}
+
+// Occlusion of the corners of a face, packed as the `ao` field of `FaceData`. Matches
+// `Chunk::ambient_occlusion`.
+fn ambient_occlusion(pos: vec3<i32>, direction: u32) -> u32 {
+    let normal = neighbours[direction];
+    let front = pos + normal;
+    var ao = 0u;
+    for (var i = 0u; i < 4; i++) {
+        // Steps from the middle of the face towards the corner along each face axis
+        let step = (vec3<i32>(face_corners[direction][i]) * 2 - 1) * (1 - abs(normal));
+        // The two face axes, in x, y, z order
+        let first_axis = select(vec3<i32>(1, 0, 0), vec3<i32>(0, 1, 0), normal.x != 0);
+        let side1 = occludes(front + step * first_axis);
+        let side2 = occludes(front + step * (1 - abs(normal) - first_axis));
+        let diagonal = occludes(front + step);
+
+        var value = 0u;
+        if !(side1 && side2) {
+            value = 3u - u32(side1) - u32(side2) - u32(diagonal);
+        }
+        ao |= value << (2u * i);
+    }
+    return ao;
+}
+
+@compute
+@workgroup_size(4, 4, 4)
+fn count(
+    @builtin(global_invocation_id) id: vec3<u32>
+) {
+    let pos = vec3<i32>(id);
+    for (var direction = 0u; direction < 6; direction++) {
+        if is_visible(pos, direction) {
+            atomicAdd(&face_counts[face_list(pos, direction)], 1u);
+        }
+    }
+}
+
+@compute
+@workgroup_size(1)
+fn offsets() {
+    face_offsets[0] = 0u;
+    for (var list = 0u; list < FACE_LISTS; list++) {
+        let count = atomicExchange(&face_counts[list], face_offsets[list]);
+        face_offsets[list + 1] = face_offsets[list] + count;
+    }
+
+    let opaque_count = face_offsets[6];
+    let face_count = face_offsets[FACE_LISTS];
+    indirect_args.workgroups_x = (face_count + GEOMETRY_WORKGROUP_SIZE - 1) / GEOMETRY_WORKGROUP_SIZE;
+    indirect_args.workgroups_y = 1u;
+    indirect_args.workgroups_z = 1u;
+    indirect_args.vertex_count = opaque_count * 6;
+    indirect_args.instance_count = 1u;
+    indirect_args.first_vertex = 0u;
+    indirect_args.first_instance = 0u;
+    indirect_args.transparent_vertex_count = (face_count - opaque_count) * 6;
+    indirect_args.transparent_instance_count = 1u;
+    indirect_args.transparent_first_vertex = opaque_count * 6;
+    indirect_args.transparent_first_instance = 0u;
+}
+
+@compute
+@workgroup_size(4, 4, 4)
+fn emit(
+    @builtin(global_invocation_id) id: vec3<u32>
+) {
+    let pos = vec3<i32>(id);
+    let block = voxel(pos);
+    for (var direction = 0u; direction < 6; direction++) {
+        if is_visible(pos, direction) {
+            let face_index = atomicAdd(&face_counts[face_list(pos, direction)], 1u);
+            let texture = (block_info[block].textures[direction / 2] >> (16 * (direction % 2))) & 0xffff;
+
+            // A face of one block, as the width and height fields store the size minus one
+            var face = vec2<u32>(0u, 0u);
+            face = face_set_x(face, id.x);
+            face = face_set_y(face, id.y);
+            face = face_set_z(face, id.z);
+            face = face_set_u(face, texture & 0xff);
+            face = face_set_v(face, texture >> 8);
+            face = face_set_ao(face, ambient_occlusion(pos, direction));
+            face = face_set_sky_light(face, sky_light(pos + neighbours[direction]));
+            face = face_set_block_light(face, block_light(pos + neighbours[direction]));
+            face_buffer[face_index] = face;
+        }
+    }
+}
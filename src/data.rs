@@ -1,17 +1,368 @@
+use std::fmt::Write;
+
+use crate::lightmap::Light;
+
+/// Version of the `FaceData` bit layout, bumped whenever a field is added or moved.
+pub const FACE_DATA_VERSION: u32 = 3;
+
+/// A bit range of one of the two words of a `FaceData`.
+#[derive(Debug, Clone, Copy)]
+pub struct FaceField {
+    pub name: &'static str,
+    pub word: usize,
+    pub shift: u32,
+    pub bits: u32,
+}
+
+impl FaceField {
+    const fn new(name: &'static str, word: usize, shift: u32, bits: u32) -> Self {
+        Self {
+            name,
+            word,
+            shift,
+            bits,
+        }
+    }
+
+    pub const fn mask(&self) -> u32 {
+        (1 << self.bits) - 1
+    }
+
+    pub fn get(&self, data: &FaceData) -> u32 {
+        (data.0[self.word] >> self.shift) & self.mask()
+    }
+
+    /// Stores `value`, truncated to the width of the field.
+    pub fn set(&self, data: &mut FaceData, value: u32) {
+        let word = &mut data.0[self.word];
+        *word = (*word & !(self.mask() << self.shift)) | ((value & self.mask()) << self.shift);
+    }
+}
+
+// Word 0: block position in the chunk, face size and ambient occlusion
+pub const X: FaceField = FaceField::new("x", 0, 0, 4);
+pub const Y: FaceField = FaceField::new("y", 0, 4, 4);
+pub const Z: FaceField = FaceField::new("z", 0, 8, 4);
+/// Width in blocks, minus one.
+pub const WIDTH: FaceField = FaceField::new("width", 0, 12, 4);
+/// Height in blocks, minus one.
+pub const HEIGHT: FaceField = FaceField::new("height", 0, 16, 4);
+/// Two bits per corner in `Direction::corners` order, from 0 (darkest) to 3 (unoccluded).
+pub const AO: FaceField = FaceField::new("ao", 0, 20, 8);
+// Bits 28..32 are reserved
+
+// Word 1: texture and light
+pub const U: FaceField = FaceField::new("u", 1, 0, 8);
+pub const V: FaceField = FaceField::new("v", 1, 8, 8);
+// Bits 16..24 are reserved
+/// Sky light of the block in front of the face.
+pub const SKY_LIGHT: FaceField = FaceField::new("sky_light", 1, 24, 4);
+/// Block light of the block in front of the face.
+pub const BLOCK_LIGHT: FaceField = FaceField::new("block_light", 1, 28, 4);
+
+pub const FIELDS: [FaceField; 10] = [X, Y, Z, WIDTH, HEIGHT, AO, U, V, SKY_LIGHT, BLOCK_LIGHT];
+
+/// One face of a block, packed into two `u32` words as laid out by `FIELDS`.
 #[repr(C)]
-#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct FaceData {
-    position: u16,
-    u: u8,
-    v: u8,
+#[derive(Clone, Copy, Default, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FaceData([u32; 2]);
+
+/// The fields of a `FaceData`, unpacked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodedFace {
+    pub x: u8,
+    pub y: u8,
+    pub z: u8,
+    /// In blocks, 1..=16.
+    pub width: u8,
+    /// In blocks, 1..=16.
+    pub height: u8,
+    pub ao: u8,
+    pub u: u8,
+    pub v: u8,
+    pub sky_light: u8,
+    pub block_light: u8,
+}
+
+impl Default for DecodedFace {
+    /// A single block face at the chunk's origin.
+    fn default() -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            z: 0,
+            width: 1,
+            height: 1,
+            ao: 0,
+            u: 0,
+            v: 0,
+            sky_light: 0,
+            block_light: 0,
+        }
+    }
 }
 
 impl FaceData {
     pub fn new(x: u8, y: u8, z: u8, u: u8, v: u8) -> Self {
-        Self {
-            position: 0u16 | (x as u16 & 15u16) | (y as u16 & 15u16 << 4) | (z as u16 & 15u16 << 8),
+        Self::encode(&DecodedFace {
+            x,
+            y,
+            z,
             u,
             v,
+            ..Default::default()
+        })
+    }
+
+    /// Sets the extent of the face along its width and height axes, each in 1..=16 blocks. A
+    /// size of 0 is stored as 1.
+    pub fn with_size(mut self, width: u8, height: u8) -> Self {
+        WIDTH.set(&mut self, extent(width));
+        HEIGHT.set(&mut self, extent(height));
+        self
+    }
+
+    /// Sets the ambient occlusion of each corner, two bits each from 0 (darkest) to 3.
+    pub fn with_ao(mut self, ao: u8) -> Self {
+        AO.set(&mut self, ao as u32);
+        self
+    }
+
+    /// Sets the light of the block in front of the face.
+    pub fn with_light(mut self, light: Light) -> Self {
+        SKY_LIGHT.set(&mut self, light.sky as u32);
+        BLOCK_LIGHT.set(&mut self, light.block as u32);
+        self
+    }
+
+    /// Packs a face, truncating fields that don't fit their bits and storing a width or height
+    /// of 0 as 1.
+    pub fn encode(face: &DecodedFace) -> Self {
+        let mut data = Self::default();
+        X.set(&mut data, face.x as u32);
+        Y.set(&mut data, face.y as u32);
+        Z.set(&mut data, face.z as u32);
+        WIDTH.set(&mut data, extent(face.width));
+        HEIGHT.set(&mut data, extent(face.height));
+        AO.set(&mut data, face.ao as u32);
+        U.set(&mut data, face.u as u32);
+        V.set(&mut data, face.v as u32);
+        SKY_LIGHT.set(&mut data, face.sky_light as u32);
+        BLOCK_LIGHT.set(&mut data, face.block_light as u32);
+        data
+    }
+
+    pub fn decode(&self) -> DecodedFace {
+        DecodedFace {
+            x: X.get(self) as u8,
+            y: Y.get(self) as u8,
+            z: Z.get(self) as u8,
+            width: WIDTH.get(self) as u8 + 1,
+            height: HEIGHT.get(self) as u8 + 1,
+            ao: AO.get(self) as u8,
+            u: U.get(self) as u8,
+            v: V.get(self) as u8,
+            sky_light: SKY_LIGHT.get(self) as u8,
+            block_light: BLOCK_LIGHT.get(self) as u8,
+        }
+    }
+
+    /// WGSL accessors for every field, operating on a face stored as a `vec2<u32>`. Getters are
+    /// named `face_<field>` and setters `face_set_<field>`.
+    pub fn wgsl() -> String {
+        let mut wgsl = format!(
+            "// Generated from `FaceData` in data.rs\n\
+             const FACE_DATA_VERSION: u32 = {FACE_DATA_VERSION}u;\n"
+        );
+        for field in FIELDS {
+            let FaceField {
+                name, word, shift, ..
+            } = field;
+            let mask = field.mask();
+            write!(
+                wgsl,
+                "\n\
+                 fn face_{name}(face: vec2<u32>) -> u32 {{\n    \
+                     return (face[{word}] >> {shift}u) & {mask}u;\n\
+                 }}\n\
+                 \n\
+                 fn face_set_{name}(face: vec2<u32>, value: u32) -> vec2<u32> {{\n    \
+                     var out = face;\n    \
+                     out[{word}] = (out[{word}] & ~({mask}u << {shift}u)) | ((value & {mask}u) << {shift}u);\n    \
+                     return out;\n\
+                 }}\n"
+            )
+            .expect("writing to a String can't fail");
+        }
+        wgsl
+    }
+}
+
+/// `WIDTH` or `HEIGHT` value of a face `blocks` long, counting an empty face as one block.
+fn extent(blocks: u8) -> u32 {
+    (blocks as u32).saturating_sub(1)
+}
+
+impl std::fmt::Debug for FaceData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.decode().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_field_round_trips_on_its_own() {
+        for field in FIELDS {
+            for value in [0, 1, field.mask() / 2, field.mask()] {
+                let mut data = FaceData::default();
+                field.set(&mut data, value);
+                assert_eq!(field.get(&data), value, "{}", field.name);
+                for other in FIELDS.iter().filter(|other| other.name != field.name) {
+                    assert_eq!(
+                        other.get(&data),
+                        0,
+                        "{} leaks into {}",
+                        field.name,
+                        other.name
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fields_fit_their_words_without_overlapping() {
+        let mut used = [0u32; 2];
+        for field in FIELDS {
+            assert!(
+                field.shift + field.bits <= 32,
+                "{} overflows its word",
+                field.name
+            );
+            let bits = field.mask() << field.shift;
+            assert_eq!(used[field.word] & bits, 0, "{} overlaps", field.name);
+            used[field.word] |= bits;
+        }
+    }
+
+    #[test]
+    fn set_truncates_to_the_field_and_keeps_the_others() {
+        let mut data = FaceData::encode(&DecodedFace {
+            u: 0xff,
+            sky_light: 0xf,
+            ..Default::default()
+        });
+        V.set(&mut data, 0x1ab);
+        assert_eq!(V.get(&data), 0xab);
+        assert_eq!(U.get(&data), 0xff);
+        assert_eq!(SKY_LIGHT.get(&data), 0xf);
+    }
+
+    #[test]
+    fn decoded_faces_round_trip() {
+        let smallest = DecodedFace::default();
+        let largest = DecodedFace {
+            x: 15,
+            y: 15,
+            z: 15,
+            width: 16,
+            height: 16,
+            ao: 0xff,
+            u: 0xff,
+            v: 0xff,
+            sky_light: 15,
+            block_light: 15,
+        };
+        let mixed = DecodedFace {
+            x: 1,
+            y: 2,
+            z: 3,
+            width: 4,
+            height: 5,
+            ao: 0b11_10_01_00,
+            u: 6,
+            v: 7,
+            sky_light: 8,
+            block_light: 9,
+        };
+        for face in [smallest, largest, mixed] {
+            assert_eq!(FaceData::encode(&face).decode(), face);
+        }
+    }
+
+    #[test]
+    fn builders_match_encode() {
+        let face = FaceData::new(1, 2, 3, 4, 5)
+            .with_size(16, 7)
+            .with_ao(0b01_10_11_00)
+            .with_light(Light { sky: 12, block: 3 });
+        assert_eq!(
+            face.decode(),
+            DecodedFace {
+                x: 1,
+                y: 2,
+                z: 3,
+                width: 16,
+                height: 7,
+                ao: 0b01_10_11_00,
+                u: 4,
+                v: 5,
+                sky_light: 12,
+                block_light: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn zero_sizes_are_stored_as_one_block() {
+        let face = FaceData::new(0, 0, 0, 0, 0).with_size(0, 0).decode();
+        assert_eq!([face.width, face.height], [1, 1]);
+
+        let face = FaceData::encode(&DecodedFace {
+            width: 0,
+            height: 0,
+            ..Default::default()
+        });
+        assert_eq!(face, FaceData::encode(&DecodedFace::default()));
+        assert_eq!(DecodedFace::default().width, 1);
+        assert_eq!(DecodedFace::default().height, 1);
+    }
+
+    /// Bumping `FACE_DATA_VERSION` means updating this layout.
+    #[test]
+    fn version_3_layout() {
+        assert_eq!(FACE_DATA_VERSION, 3);
+        let face = FaceData::encode(&DecodedFace {
+            x: 1,
+            y: 2,
+            z: 3,
+            width: 4,
+            height: 5,
+            ao: 0b11_10_01_00,
+            u: 6,
+            v: 7,
+            sky_light: 8,
+            block_light: 9,
+        });
+        assert_eq!(
+            face.0,
+            [
+                1 | 2 << 4 | 3 << 8 | 3 << 12 | 4 << 16 | 0b11_10_01_00 << 20,
+                6 | 7 << 8 | 8 << 24 | 9 << 28,
+            ]
+        );
+    }
+
+    #[test]
+    fn wgsl_has_the_version_and_every_field() {
+        let wgsl = FaceData::wgsl();
+        assert!(wgsl.contains("const FACE_DATA_VERSION: u32 = 3u;"));
+        for field in FIELDS {
+            assert!(wgsl.contains(&format!("fn face_{}(", field.name)));
+            assert!(wgsl.contains(&format!("fn face_set_{}(", field.name)));
         }
     }
 }
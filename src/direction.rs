@@ -1,3 +1,13 @@
+use std::fmt::Write;
+
+/// Corner of a face for each of the six vertices of its two triangles, split along the diagonal
+/// from corner 1 to 2 into the triangles of `Direction::corners`.
+pub const QUAD_CORNERS: [usize; 6] = [0, 1, 2, 2, 1, 3];
+/// `QUAD_CORNERS` split along the diagonal from corner 0 to 3 instead, wound the same way.
+pub const FLIPPED_QUAD_CORNERS: [usize; 6] = [0, 1, 3, 0, 3, 2];
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Direction {
     UP,
     DOWN,
@@ -8,6 +18,16 @@ pub enum Direction {
 }
 
 impl Direction {
+    /// All directions, in the order their face lists are laid out on the GPU.
+    pub const ALL: [Direction; 6] = [
+        Direction::UP,
+        Direction::DOWN,
+        Direction::LEFT,
+        Direction::RIGHT,
+        Direction::FRONT,
+        Direction::BACK,
+    ];
+
     pub fn name(&self) -> &str {
         match self {
             Direction::UP => "Up",
@@ -18,4 +38,57 @@ impl Direction {
             Direction::BACK => "Back",
         }
     }
+
+    /// Unit step towards the neighbouring block on this side.
+    pub fn offset(&self) -> [i32; 3] {
+        match self {
+            Direction::UP => [0, 1, 0],
+            Direction::DOWN => [0, -1, 0],
+            Direction::LEFT => [-1, 0, 0],
+            Direction::RIGHT => [1, 0, 0],
+            Direction::FRONT => [0, 0, 1],
+            Direction::BACK => [0, 0, -1],
+        }
+    }
+
+    /// Corners of the unit face on this side of a block, in the order of the AO bits of
+    /// `FaceData`. The triangles (0, 1, 2) and (2, 1, 3) are wound counter-clockwise when seen
+    /// from outside the block.
+    pub fn corners(&self) -> [[i32; 3]; 4] {
+        match self {
+            Direction::UP => [[0, 1, 0], [0, 1, 1], [1, 1, 0], [1, 1, 1]],
+            Direction::DOWN => [[0, 0, 0], [1, 0, 0], [0, 0, 1], [1, 0, 1]],
+            Direction::LEFT => [[0, 0, 0], [0, 0, 1], [0, 1, 0], [0, 1, 1]],
+            Direction::RIGHT => [[1, 0, 0], [1, 1, 0], [1, 0, 1], [1, 1, 1]],
+            Direction::FRONT => [[0, 0, 1], [1, 0, 1], [0, 1, 1], [1, 1, 1]],
+            Direction::BACK => [[0, 0, 0], [0, 1, 0], [1, 0, 0], [1, 1, 0]],
+        }
+    }
+
+    /// `corners` of every direction as the WGSL constant `face_corners`, indexed by direction
+    /// and then corner, followed by `QUAD_CORNERS` and `FLIPPED_QUAD_CORNERS`.
+    pub fn wgsl() -> String {
+        let mut wgsl = String::from(
+            "// Generated from `Direction` in direction.rs\n\
+             const face_corners: array<array<vec3<f32>, 4>, 6> = array(\n",
+        );
+        for direction in Direction::ALL {
+            let corners = direction
+                .corners()
+                .map(|[x, y, z]| format!("vec3<f32>({x}, {y}, {z})"))
+                .join(", ");
+            writeln!(wgsl, "    array({corners}), // {}", direction.name())
+                .expect("writing to a String can't fail");
+        }
+        wgsl.push_str(");\n");
+        for (name, corners) in [
+            ("quad_corners", QUAD_CORNERS),
+            ("flipped_quad_corners", FLIPPED_QUAD_CORNERS),
+        ] {
+            let corners = corners.map(|corner| format!("{corner}u")).join(", ");
+            writeln!(wgsl, "const {name}: array<u32, 6> = array({corners});")
+                .expect("writing to a String can't fail");
+        }
+        wgsl
+    }
 }
@@ -0,0 +1,105 @@
+// Face decoding shared by the geometry pass and the vertex pulling render path
+
+// Matches `FACE_LISTS` in chunk.rs: the opaque faces of each direction, then the transparent
+// faces of each direction
+const FACE_LISTS: u32 = 12;
+
+// Start of each face list, followed by the total face count
+@group(0)
+@binding(0)
+var<storage> face_offsets: array<u32, 13>;
+
+@group(0)
+@binding(1)
+// Laid out as `FaceData`, read through the generated `face_*` accessors
+var<storage> face_buffer: array<vec2<u32>>;
+
+@group(0)
+@binding(2)
+var<uniform> chunk_origin: vec3<i32>;
+
+const normals: array<vec3<f32>, 6> = array(
+    vec3<f32>(0, 1, 0), // Up
+    vec3<f32>(0, -1, 0), // Down
+    vec3<f32>(-1, 0, 0), // Left
+    vec3<f32>(1, 0, 0), // Right
+    vec3<f32>(0, 0, 1), // Front
+    vec3<f32>(0, 0, -1), // Back
+);
+
+// Axes along which a face in each direction is stretched by its width and height
+const width_axes: array<vec3<f32>, 6> = array(
+    vec3<f32>(1, 0, 0), // Up
+    vec3<f32>(1, 0, 0), // Down
+    vec3<f32>(0, 0, 1), // Left
+    vec3<f32>(0, 0, 1), // Right
+    vec3<f32>(1, 0, 0), // Front
+    vec3<f32>(1, 0, 0), // Back
+);
+const height_axes: array<vec3<f32>, 6> = array(
+    vec3<f32>(0, 0, 1), // Up
+    vec3<f32>(0, 0, 1), // Down
+    vec3<f32>(0, 1, 0), // Left
+    vec3<f32>(0, 1, 0), // Right
+    vec3<f32>(0, 1, 0), // Front
+    vec3<f32>(0, 1, 0), // Back
+);
+
+struct FaceVertices {
+    positions: array<vec3<f32>, 4>,
+    // Counted in blocks, so textures repeat across merged faces
+    tiles: array<vec2<f32>, 4>,
+    normal: vec3<f32>,
+    // Atlas tile, as u | v << 8
+    texture: u32,
+    // From 0 (darkest) to 3 (unoccluded)
+    ao: array<f32, 4>,
+    // Sky and block light in front of the face, as sky | block << 4
+    light: u32,
+}
+
+// Direction of the face list containing `face`
+fn face_direction(face: u32) -> u32 {
+    var list = 0u;
+    while face >= face_offsets[list + 1] {
+        list++;
+    }
+    return list % 6;
+}
+
+// Corners of `face` in world space
+fn face_vertices(face: u32, direction: u32) -> FaceVertices {
+    let data = face_buffer[face];
+    let face_pos = vec3<f32>(
+        f32(face_x(data)),
+        f32(face_y(data)),
+        f32(face_z(data))
+    ) + vec3<f32>(chunk_origin);
+
+    let extent = vec2<f32>(f32(face_width(data) + 1), f32(face_height(data) + 1));
+    let scale = vec3<f32>(1, 1, 1)
+        + width_axes[direction] * (extent.x - 1)
+        + height_axes[direction] * (extent.y - 1);
+
+    var vertices: FaceVertices;
+    for (var i = 0; i < 4; i++) {
+        let corner = face_corners[direction][i];
+        vertices.positions[i] = face_pos + corner * scale;
+        vertices.tiles[i] = vec2<f32>(dot(corner, width_axes[direction]), dot(corner, height_axes[direction])) * extent;
+        vertices.ao[i] = f32((face_ao(data) >> (2u * u32(i))) & 3u);
+    }
+    vertices.normal = normals[direction];
+    vertices.texture = face_u(data) | (face_v(data) << 8);
+    vertices.light = face_sky_light(data) | (face_block_light(data) << 4);
+    return vertices;
+}
+
+// Corner of `vertices` for the vertex at `index` of its two triangles. The quad is split along
+// the diagonal joining its brighter corners, so a single occluded corner darkens one triangle
+// instead of bleeding across the whole face.
+fn quad_corner(vertices: FaceVertices, index: u32) -> u32 {
+    if vertices.ao[0] + vertices.ao[3] > vertices.ao[1] + vertices.ao[2] {
+        return flipped_quad_corners[index];
+    }
+    return quad_corners[index];
+}
@@ -1,93 +1,40 @@
 
-@group(0)
+// Apart from the face group, which the render passes bind while reading these as vertices
+@group(1)
 @binding(0)
-var<storage> face_offsets: array<u32, 7>;
-
-@group(0)
-@binding(1)
-var<storage> face_buffer: array<u32>;
-
-@group(0)
-@binding(2)
 var<storage, read_write> vertex_buffer: array<f32>;
 
-const mats: array<mat4x3<f32>, 6> = array(
-    // Up
-    mat4x3(
-        vec3<f32>(0, 0, 0),
-        vec3<f32>(0, 0, 0),
-        vec3<f32>(0, 0, 0),
-        vec3<f32>(0, 0, 0),
-    ),
-    // Down
-    mat4x3(
-        vec3<f32>(0, 0, 0),
-        vec3<f32>(0, 0, 0),
-        vec3<f32>(0, 0, 0),
-        vec3<f32>(0, 0, 0),
-    ),
-    // Left
-    mat4x3(
-        vec3<f32>(0, 0, 0),
-        vec3<f32>(0, 0, 0),
-        vec3<f32>(0, 0, 0),
-        vec3<f32>(0, 0, 0),
-    ),
-    // Right
-    mat4x3(
-        vec3<f32>(0, 0, 0),
-        vec3<f32>(0, 0, 0),
-        vec3<f32>(0, 0, 0),
-        vec3<f32>(0, 0, 0),
-    ),
-    // Front
-    mat4x3(
-        vec3<f32>(0, 0, 0),
-        vec3<f32>(0.5, 0, 0),
-        vec3<f32>(0, 0.5, 0),
-        vec3<f32>(0.5, 0.5, 0),
-    ),
-    // Back
-    mat4x3(
-        vec3<f32>(0, 0, 0),
-        vec3<f32>(0, 0.5, 0),
-        vec3<f32>(0.5, 0, 0),
-        vec3<f32>(0.5, 0.5, 0),
-    ),
-);
-
+// Matches `GEOMETRY_WORKGROUP_SIZE` in render.rs
 @compute
-@workgroup_size(1)
+@workgroup_size(64)
 fn main(
     @builtin(global_invocation_id) id: vec3<u32>
 ) {
-    let offset = face_offsets[id.x];
-    let len = face_offsets[id.x + 1] - offset;
-    if id.y < len {
-        let face_index = (offset +  id.y); // Counted in u32
-        let vertex_index = (offset + id.y) * 6; // Counted in f32x3
+    let face = id.x;
+    if face < face_offsets[FACE_LISTS] {
+        let direction = face_direction(face);
+        let vertex_index = face * 6; // Counted in vertices
 
-        let face_pos = vec3<f32>(
-            f32(face_buffer[face_index] & 15),
-            f32(face_buffer[face_index] & (15 << 4) >> 4),
-            f32(face_buffer[face_index] & (15 << 8) >> 8)
-        );
+        let vertices = face_vertices(face, direction);
 
-        let mat = mat4x3<f32>(face_pos, face_pos, face_pos, face_pos);
-
-        let vertices = mat + mats[id.x];
-
-        set_vertex(vertex_index, vertices[0]);
-        set_vertex(vertex_index, vertices[1]);
-        set_vertex(vertex_index, vertices[2]);
-        set_vertex(vertex_index, vertices[2]);
-        set_vertex(vertex_index, vertices[1]);
-        set_vertex(vertex_index, vertices[3]);
+        for (var i = 0u; i < 6; i++) {
+            let corner = quad_corner(vertices, i);
+            set_vertex(vertex_index + i, vertices.positions[corner], vertices.tiles[corner], vertices.normal, vertices.texture, vertices.ao[corner], vertices.light);
+        }
     }
 }
-
-fn set_vertex(index: u32, vertex: vec3<f32>) {
-    vertex_buffer[index * 3] = vertex.x;
-    vertex_buffer[index * 3 + 1] = vertex.y;
-    vertex_buffer[index * 3 + 2] = vertex.z;
+// Matches the vertex buffer layout of the render pipeline in app.rs
+fn set_vertex(index: u32, vertex: vec3<f32>, tile: vec2<f32>, normal: vec3<f32>, texture: u32, ao: f32, light: u32) {
+    vertex_buffer[index * 11] = vertex.x;
+    vertex_buffer[index * 11 + 1] = vertex.y;
+    vertex_buffer[index * 11 + 2] = vertex.z;
+    vertex_buffer[index * 11 + 3] = tile.x;
+    vertex_buffer[index * 11 + 4] = tile.y;
+    vertex_buffer[index * 11 + 5] = normal.x;
+    vertex_buffer[index * 11 + 6] = normal.y;
+    vertex_buffer[index * 11 + 7] = normal.z;
+    // Read back as a u32 attribute
+    vertex_buffer[index * 11 + 8] = bitcast<f32>(texture);
+    vertex_buffer[index * 11 + 9] = ao;
+    vertex_buffer[index * 11 + 10] = bitcast<f32>(light);
 }
@@ -0,0 +1,189 @@
+use std::{f32::consts::TAU, num::NonZero};
+
+use glam::Vec3;
+
+use crate::shadow::ShadowMap;
+
+/// Lean of the sun's path towards +Z, so the front and back faces are lit differently.
+const SUN_TILT: f32 = 0.4;
+
+/// Zenith and horizon colours of the sky at noon, at midnight, and on the horizon at sunrise
+/// and sunset.
+const DAY_ZENITH: Vec3 = Vec3::new(0.18, 0.36, 0.75);
+const DAY_HORIZON: Vec3 = Vec3::new(0.62, 0.74, 0.9);
+const NIGHT_ZENITH: Vec3 = Vec3::new(0.005, 0.008, 0.02);
+const NIGHT_HORIZON: Vec3 = Vec3::new(0.03, 0.04, 0.08);
+const TWILIGHT_HORIZON: Vec3 = Vec3::new(0.9, 0.45, 0.25);
+/// Fraction of the ambient light left at night, from the moon and stars.
+const NIGHT_AMBIENT: f32 = 0.15;
+
+/// A sun crossing the sky once per day, the ambient light filling in its shadows, and the sky
+/// and fog behind it all.
+#[derive(Debug, Clone)]
+pub struct Lighting {
+    /// Fraction of the day, where 0.25 is sunrise in +X, 0.5 is noon and 0.75 is sunset.
+    pub time_of_day: f32,
+    /// Linear colour of direct sunlight at day.
+    pub sun_color: Vec3,
+    /// Linear colour of the light reaching every face open to the sky at day.
+    pub ambient: Vec3,
+    /// Linear colour of the light given off by emissive blocks.
+    pub block_light_color: Vec3,
+    /// Distance from the camera in blocks where fog starts to blend faces into the sky.
+    pub fog_start: f32,
+    /// Distance from the camera in blocks beyond which fog hides faces completely.
+    pub fog_end: f32,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self {
+            time_of_day: 0.375,
+            sun_color: Vec3::new(1.0, 0.95, 0.85),
+            ambient: Vec3::new(0.35, 0.38, 0.45),
+            block_light_color: Vec3::new(1.0, 0.8, 0.55),
+            fog_start: 64.0,
+            fog_end: 160.0,
+        }
+    }
+}
+
+impl Lighting {
+    /// Unit vector pointing at the sun, below the horizon at night.
+    pub fn sun_direction(&self) -> Vec3 {
+        let (sin, cos) = ((self.time_of_day - 0.25) * TAU).sin_cos();
+        Vec3::new(cos, sin, SUN_TILT).normalize()
+    }
+
+    /// How much sunlight reaches the world, from 1 while the sun is up to 0 once it has set.
+    pub fn daylight(&self) -> f32 {
+        smoothstep(-0.1, 0.1, self.sun_direction().y)
+    }
+
+    /// Direct sunlight, faded out as the sun sets so that it never shines up through the ground.
+    pub fn sun_light(&self) -> Vec3 {
+        self.sun_color * self.daylight()
+    }
+
+    /// Ambient light, dimmed to `NIGHT_AMBIENT` of `ambient` at night.
+    pub fn ambient_light(&self) -> Vec3 {
+        self.ambient * (NIGHT_AMBIENT + (1.0 - NIGHT_AMBIENT) * self.daylight())
+    }
+
+    /// Zenith and horizon colours of the sky, blue by day and dark at night, with the horizon
+    /// reddening while the sun is close to it.
+    pub fn sky_colors(&self) -> (Vec3, Vec3) {
+        let height = self.sun_direction().y;
+        let day = smoothstep(-0.15, 0.25, height);
+        let twilight = (1.0 - height.abs() / 0.25).max(0.0);
+
+        let zenith = NIGHT_ZENITH.lerp(DAY_ZENITH, day);
+        let horizon = NIGHT_HORIZON
+            .lerp(DAY_HORIZON, day)
+            .lerp(TWILIGHT_HORIZON, twilight * 0.6);
+        (zenith, horizon)
+    }
+
+    /// Moves the sun by `hours` of a 24 hour day, wrapping around midnight.
+    pub fn advance(&mut self, hours: f32) {
+        self.time_of_day = (self.time_of_day + hours / 24.0).rem_euclid(1.0);
+    }
+}
+
+/// Hermite interpolation from 0 at `edge0` to 1 at `edge1`, like WGSL's `smoothstep`.
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Lighting data laid out as `Lighting` in `lighting.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightingUniform {
+    sun_direction: [f32; 4],
+    sun_color: [f32; 4],
+    ambient: [f32; 4],
+    block_light_color: [f32; 4],
+    sky_zenith: [f32; 4],
+    sky_horizon: [f32; 3],
+    fog_start: f32,
+    fog_end: f32,
+    _padding: [f32; 3],
+}
+
+/// The uniform buffer and bind group exposing `Lighting` and the `ShadowMap` to the render
+/// pipelines.
+#[derive(Debug)]
+pub struct LightingBinding {
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl LightingBinding {
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let lighting = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: NonZero::new(std::mem::size_of::<LightingUniform>() as u64),
+            },
+            count: None,
+        };
+        let [shadow_map, shadow_sampler, shadows] = ShadowMap::bind_group_layout_entries(1);
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Lighting Group Layout"),
+            entries: &[lighting, shadow_map, shadow_sampler, shadows],
+        })
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        shadow_map: &ShadowMap,
+    ) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lighting Buffer"),
+            size: std::mem::size_of::<LightingUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let [shadow_map, shadow_sampler, shadows] = shadow_map.bind_group_entries(1);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Lighting Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                shadow_map,
+                shadow_sampler,
+                shadows,
+            ],
+        });
+
+        Self { buffer, bind_group }
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, lighting: &Lighting) {
+        let (sky_zenith, sky_horizon) = lighting.sky_colors();
+        let uniform = LightingUniform {
+            sun_direction: lighting.sun_direction().extend(0.0).into(),
+            sun_color: lighting.sun_light().extend(0.0).into(),
+            ambient: lighting.ambient_light().extend(0.0).into(),
+            block_light_color: lighting.block_light_color.extend(0.0).into(),
+            sky_zenith: sky_zenith.extend(0.0).into(),
+            sky_horizon: sky_horizon.into(),
+            fog_start: lighting.fog_start,
+            fog_end: lighting.fog_end,
+            _padding: [0.0; 3],
+        };
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+}
@@ -0,0 +1,29 @@
+// Lighting and sky colour shared by the render and sky pipelines
+
+// Laid out as `LightingUniform` in light.rs
+struct Lighting {
+    // Towards the sun
+    sun_direction: vec3<f32>,
+    // Faded out at night, and the ambient light dimmed
+    sun_color: vec3<f32>,
+    ambient: vec3<f32>,
+    block_light_color: vec3<f32>,
+    sky_zenith: vec3<f32>,
+    sky_horizon: vec3<f32>,
+    // Distances from the camera where fog starts and where it hides everything
+    fog_start: f32,
+    fog_end: f32,
+}
+
+// Colour of the sky in `direction`, graded from the horizon up to the zenith, with a glow
+// around the sun
+fn sky_color(lighting: Lighting, direction: vec3<f32>) -> vec3<f32> {
+    // Below the horizon stays at the horizon colour, so fog in the distance matches it
+    let height = clamp(direction.y, 0.0, 1.0);
+    let gradient = mix(lighting.sky_horizon, lighting.sky_zenith, sqrt(height));
+
+    let towards_sun = max(dot(direction, lighting.sun_direction), 0.0);
+    let glow = pow(towards_sun, 8.0) * 0.25 + pow(towards_sun, 512.0) * 4.0;
+    // Fades out with the sun colour as the sun sets
+    return gradient + lighting.sun_color * glow;
+}
@@ -0,0 +1,497 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::{
+    block::{Block, BlockId, BlockRegistry},
+    chunk::CHUNK_SIZE,
+    direction::Direction,
+    world::{ChunkPos, World},
+};
+
+/// Light level of open sky and of the brightest light sources.
+pub const MAX_LIGHT: u8 = 15;
+
+/// Light levels of a block, each in 0..=`MAX_LIGHT`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Light {
+    /// Light from the sky, full strength straight below open sky and dimming by one per block
+    /// it spreads sideways or around corners.
+    pub sky: u8,
+    /// Light from emissive blocks, dimming by one per block.
+    pub block: u8,
+}
+
+impl Light {
+    /// Light of the open sky, which is what every block outside the loaded chunks gets.
+    pub const SKY: Self = Self {
+        sky: MAX_LIGHT,
+        block: 0,
+    };
+}
+
+/// The two kinds of light, which spread independently of each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Channel {
+    Sky,
+    Block,
+}
+
+impl Channel {
+    const ALL: [Channel; 2] = [Channel::Sky, Channel::Block];
+
+    fn get(self, light: Light) -> u8 {
+        match self {
+            Channel::Sky => light.sky,
+            Channel::Block => light.block,
+        }
+    }
+
+    fn set(self, light: &mut Light, level: u8) {
+        match self {
+            Channel::Sky => light.sky = level,
+            Channel::Block => light.block = level,
+        }
+    }
+
+    fn emission(self, block: &Block) -> u8 {
+        match self {
+            Channel::Sky => 0,
+            Channel::Block => block.emission,
+        }
+    }
+
+    /// Level reaching the next block in `direction` from a block lit at `level`. Full sky light
+    /// shines straight down without dimming.
+    fn spread(self, level: u8, direction: Direction) -> u8 {
+        match self {
+            Channel::Sky if level == MAX_LIGHT && direction == Direction::DOWN => MAX_LIGHT,
+            _ => level.saturating_sub(1),
+        }
+    }
+}
+
+fn step([x, y, z]: [i32; 3], direction: Direction) -> [i32; 3] {
+    let [dx, dy, dz] = direction.offset();
+    [x + dx, y + dy, z + dz]
+}
+
+/// Position of a world block within its chunk.
+fn local([x, y, z]: [i32; 3]) -> [usize; 3] {
+    let size = CHUNK_SIZE as i32;
+    [x, y, z].map(|n| n.rem_euclid(size) as usize)
+}
+
+/// Breadth-first flood fill of light through the non-opaque blocks of the loaded chunks.
+struct Propagation<'a> {
+    world: &'a mut World,
+    registry: &'a BlockRegistry,
+    /// Chunks whose light was changed.
+    changed: HashSet<ChunkPos>,
+}
+
+impl<'a> Propagation<'a> {
+    fn new(world: &'a mut World, registry: &'a BlockRegistry) -> Self {
+        Self {
+            world,
+            registry,
+            changed: HashSet::new(),
+        }
+    }
+
+    fn block(&self, [x, y, z]: [i32; 3]) -> &Block {
+        self.registry.get(self.world.get(x, y, z))
+    }
+
+    /// Light level of a block, or `None` where no chunk is loaded.
+    fn level(&self, pos: [i32; 3], channel: Channel) -> Option<u8> {
+        let [x, y, z] = pos;
+        let chunk = self.world.chunk(ChunkPos::of_block(x, y, z))?;
+        let [x, y, z] = local(pos);
+        Some(channel.get(chunk.light(x, y, z)))
+    }
+
+    fn set_level(&mut self, pos: [i32; 3], channel: Channel, level: u8) {
+        let [x, y, z] = pos;
+        let chunk_pos = ChunkPos::of_block(x, y, z);
+        let chunk = self
+            .world
+            .chunk_mut(chunk_pos)
+            .expect("light is only set in loaded chunks");
+        let [x, y, z] = local(pos);
+        let mut light = chunk.light(x, y, z);
+        channel.set(&mut light, level);
+        chunk.set_light(x, y, z, light);
+        self.changed.insert(chunk_pos);
+    }
+
+    /// Light a block gets regardless of its loaded neighbours: its own emission, and the sky
+    /// light shining down from an unloaded chunk above it unless it is opaque. Unloaded chunks
+    /// below and to the sides are dark, so caves along the edges of the loaded area stay unlit.
+    fn source(&self, pos: [i32; 3], channel: Channel) -> u8 {
+        let block = self.block(pos);
+        let mut level = channel.emission(block);
+        if !block.opaque && self.level(step(pos, Direction::UP), channel).is_none() {
+            let outside = channel.get(Light::SKY);
+            level = level.max(channel.spread(outside, Direction::DOWN));
+        }
+        level
+    }
+
+    /// Spreads light from every block in `queue` to its neighbours, and on from every
+    /// neighbour it brightens.
+    fn add(&mut self, channel: Channel, mut queue: VecDeque<[i32; 3]>) {
+        while let Some(pos) = queue.pop_front() {
+            let Some(level) = self.level(pos, channel) else {
+                continue;
+            };
+            for direction in Direction::ALL {
+                let next = step(pos, direction);
+                let Some(next_level) = self.level(next, channel) else {
+                    continue;
+                };
+                let spread = channel.spread(level, direction);
+                if spread > next_level && !self.block(next).opaque {
+                    self.set_level(next, channel, spread);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    /// Darkens every block that may have been lit by the blocks in `queue`, which have
+    /// already been darkened from the level they are queued with. Returns the blocks whose
+    /// light has to be spread again to fill the darkened area back in.
+    fn remove(
+        &mut self,
+        channel: Channel,
+        mut queue: VecDeque<([i32; 3], u8)>,
+    ) -> VecDeque<[i32; 3]> {
+        let mut relight = VecDeque::new();
+        let mut sources = Vec::new();
+        while let Some((pos, level)) = queue.pop_front() {
+            for direction in Direction::ALL {
+                let next = step(pos, direction);
+                let Some(next_level) = self.level(next, channel) else {
+                    continue;
+                };
+                if next_level == 0 {
+                    continue;
+                }
+
+                if next_level <= channel.spread(level, direction) {
+                    self.set_level(next, channel, 0);
+                    queue.push_back((next, next_level));
+                    sources.push(next);
+                } else {
+                    // Lit from elsewhere, so it can light the darkened blocks again
+                    relight.push_back(next);
+                }
+            }
+        }
+
+        for pos in sources {
+            let source = self.source(pos, channel);
+            if source > self.level(pos, channel).unwrap_or(0) {
+                self.set_level(pos, channel, source);
+                relight.push_back(pos);
+            }
+        }
+        relight
+    }
+}
+
+/// Recomputes the light of every loaded chunk from scratch.
+pub fn relight(world: &mut World, registry: &BlockRegistry) {
+    let positions: Vec<ChunkPos> = world.chunks().map(|(&pos, _)| pos).collect();
+    for &pos in &positions {
+        world
+            .chunk_mut(pos)
+            .expect("collected from the loaded chunks")
+            .clear_light();
+    }
+
+    let mut propagation = Propagation::new(world, registry);
+    for channel in Channel::ALL {
+        let mut queue = VecDeque::new();
+        for pos in &positions {
+            let [ox, oy, oz] = pos.origin();
+            for z in 0..CHUNK_SIZE as i32 {
+                for y in 0..CHUNK_SIZE as i32 {
+                    for x in 0..CHUNK_SIZE as i32 {
+                        let block = [ox + x, oy + y, oz + z];
+                        let source = propagation.source(block, channel);
+                        if source > 0 {
+                            propagation.set_level(block, channel, source);
+                            queue.push_back(block);
+                        }
+                    }
+                }
+            }
+        }
+        propagation.add(channel, queue);
+    }
+}
+
+/// Sets the block at a world position and updates the light around it, returning every chunk
+/// whose blocks or light changed. Placing a block outside the loaded chunks loads a new chunk
+/// and relights the whole world.
+pub fn set_block(
+    world: &mut World,
+    registry: &BlockRegistry,
+    [x, y, z]: [i32; 3],
+    block: BlockId,
+) -> HashSet<ChunkPos> {
+    let chunk_pos = ChunkPos::of_block(x, y, z);
+    if world.chunk(chunk_pos).is_none() {
+        world.set(x, y, z, block);
+        relight(world, registry);
+        return world.chunks().map(|(&pos, _)| pos).collect();
+    }
+    world.set(x, y, z, block);
+
+    let pos = [x, y, z];
+    let mut propagation = Propagation::new(world, registry);
+    for channel in Channel::ALL {
+        // Take back all the light that passed through or came from the old block
+        let mut removed = VecDeque::new();
+        let level = propagation.level(pos, channel).unwrap_or(0);
+        if level > 0 {
+            propagation.set_level(pos, channel, 0);
+            removed.push_back((pos, level));
+        }
+        let mut queue = propagation.remove(channel, removed);
+
+        // Then fill it in again from the new block and its neighbours
+        let source = propagation.source(pos, channel);
+        if source > 0 {
+            propagation.set_level(pos, channel, source);
+            queue.push_back(pos);
+        }
+        queue.extend(Direction::ALL.map(|direction| step(pos, direction)));
+        propagation.add(channel, queue);
+    }
+
+    let mut changed = propagation.changed;
+    changed.insert(chunk_pos);
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{
+        AIR,
+        testing::{self, TestBlocks},
+    };
+
+    /// One chunk at the origin walled in by stone along all six of its sides, lit from scratch.
+    fn boxed(registry: &BlockRegistry, stone: BlockId) -> World {
+        let mut world = World::default();
+        let last = CHUNK_SIZE as i32 - 1;
+        for z in 0..=last {
+            for y in 0..=last {
+                for x in 0..=last {
+                    if [x, y, z].iter().any(|&n| n == 0 || n == last) {
+                        world.set(x, y, z, stone);
+                    }
+                }
+            }
+        }
+        relight(&mut world, registry);
+        world
+    }
+
+    /// The chunks at the origin and next to it along x, under a stone roof at their top layer,
+    /// lit from scratch.
+    fn roofed_pair(registry: &BlockRegistry, stone: BlockId) -> World {
+        let mut world = World::default();
+        for z in 0..CHUNK_SIZE as i32 {
+            for x in 0..2 * CHUNK_SIZE as i32 {
+                world.set(x, CHUNK_SIZE as i32 - 1, z, stone);
+            }
+        }
+        relight(&mut world, registry);
+        world
+    }
+
+    /// Light of every block in the loaded chunks.
+    fn snapshot(world: &World) -> Vec<(ChunkPos, Vec<Light>)> {
+        let mut chunks: Vec<_> = world
+            .chunks()
+            .map(|(&pos, chunk)| {
+                let size = CHUNK_SIZE;
+                let light = (0..size * size * size)
+                    .map(|n| chunk.light(n % size, n / size % size, n / (size * size)))
+                    .collect();
+                (pos, light)
+            })
+            .collect();
+        chunks.sort_by_key(|&(pos, _)| pos.origin());
+        chunks
+    }
+
+    /// Asserts that the light left by `set_block` matches lighting the world from scratch.
+    fn assert_matches_relight(world: &World, registry: &BlockRegistry) {
+        let mut fresh = World::default();
+        for (&pos, chunk) in world.chunks() {
+            let [ox, oy, oz] = pos.origin();
+            for z in 0..CHUNK_SIZE {
+                for y in 0..CHUNK_SIZE {
+                    for x in 0..CHUNK_SIZE {
+                        let [x, y, z] = [x, y, z].map(|n| n as i32);
+                        fresh.set(
+                            ox + x,
+                            oy + y,
+                            oz + z,
+                            chunk.get(x as usize, y as usize, z as usize),
+                        );
+                    }
+                }
+            }
+        }
+        relight(&mut fresh, registry);
+        assert!(snapshot(world) == snapshot(&fresh));
+    }
+
+    #[test]
+    fn sky_light_shines_down_open_columns() {
+        let (registry, TestBlocks { stone, .. }) = testing::registry();
+        let mut world = World::default();
+        world.set(5, 12, 5, stone);
+        relight(&mut world, &registry);
+
+        assert_eq!(world.light(6, 0, 6).sky, MAX_LIGHT);
+        assert_eq!(world.light(5, 13, 5).sky, MAX_LIGHT);
+        assert_eq!(world.light(5, 12, 5).sky, 0);
+        // Shaded below the block, but lit from the open columns beside it
+        assert_eq!(world.light(5, 11, 5).sky, MAX_LIGHT - 1);
+        assert_eq!(world.light(5, 0, 5).sky, MAX_LIGHT - 1);
+    }
+
+    #[test]
+    fn unloaded_chunks_only_light_from_above() {
+        let (registry, TestBlocks { stone, .. }) = testing::registry();
+        let mut world = World::default();
+        for z in 0..CHUNK_SIZE as i32 {
+            for x in 0..CHUNK_SIZE as i32 {
+                world.set(x, CHUNK_SIZE as i32 - 1, z, stone);
+            }
+        }
+        relight(&mut world, &registry);
+
+        // Open to the unloaded chunks at the sides and below, but those are dark
+        assert_eq!(world.light(0, 5, 0).sky, 0);
+        assert_eq!(world.light(8, 0, 8).sky, 0);
+        assert_eq!(world.light(8, 8, 8), Light::default());
+    }
+
+    #[test]
+    fn sky_light_spreads_sideways_from_a_hole() {
+        let (registry, TestBlocks { stone, .. }) = testing::registry();
+        let mut world = boxed(&registry, stone);
+        let changed = set_block(&mut world, &registry, [8, 15, 8], AIR);
+
+        assert_eq!(changed, HashSet::from([ChunkPos::new(0, 0, 0)]));
+        assert_eq!(world.light(8, 15, 8).sky, MAX_LIGHT);
+        assert_eq!(world.light(8, 1, 8).sky, MAX_LIGHT);
+        assert_eq!(world.light(9, 5, 8).sky, MAX_LIGHT - 1);
+        assert_eq!(world.light(12, 5, 8).sky, MAX_LIGHT - 4);
+        assert_eq!(world.light(10, 5, 10).sky, MAX_LIGHT - 4);
+        assert_matches_relight(&world, &registry);
+    }
+
+    #[test]
+    fn block_light_falls_off_with_distance() {
+        let (registry, TestBlocks { stone, lamp, .. }) = testing::registry();
+        let mut world = boxed(&registry, stone);
+        set_block(&mut world, &registry, [8, 8, 8], lamp);
+
+        assert_eq!(world.light(8, 8, 8).block, 14);
+        assert_eq!(world.light(11, 8, 8).block, 11);
+        assert_eq!(world.light(10, 9, 8).block, 11);
+        assert_eq!(world.light(1, 8, 8).block, 7);
+        assert_eq!(world.light(0, 0, 0).block, 0);
+        // Block light leaves the sky light alone
+        assert_eq!(world.light(9, 8, 8).sky, 0);
+        assert_matches_relight(&world, &registry);
+    }
+
+    #[test]
+    fn placing_a_block_takes_light_away() {
+        let (registry, TestBlocks { stone, lamp, .. }) = testing::registry();
+        let mut world = boxed(&registry, stone);
+        set_block(&mut world, &registry, [8, 15, 8], AIR);
+        set_block(&mut world, &registry, [4, 4, 4], lamp);
+
+        // Plugging the hole from below darkens the whole room again
+        set_block(&mut world, &registry, [8, 14, 8], stone);
+        assert_eq!(world.light(8, 15, 8).sky, MAX_LIGHT);
+        assert_eq!(world.light(8, 13, 8).sky, 0);
+        assert_eq!(world.light(12, 5, 8).sky, 0);
+        assert_matches_relight(&world, &registry);
+
+        // Replacing the lamp puts its light out
+        set_block(&mut world, &registry, [4, 4, 4], stone);
+        assert_eq!(world.light(5, 4, 4).block, 0);
+        assert_eq!(world.light(4, 4, 4), Light::default());
+        assert_matches_relight(&world, &registry);
+    }
+
+    #[test]
+    fn walls_block_light() {
+        let (registry, TestBlocks { stone, lamp, .. }) = testing::registry();
+        let mut world = boxed(&registry, stone);
+        set_block(&mut world, &registry, [2, 8, 8], lamp);
+        for z in 0..CHUNK_SIZE as i32 {
+            for y in 0..CHUNK_SIZE as i32 {
+                set_block(&mut world, &registry, [4, y, z], stone);
+            }
+        }
+
+        assert_eq!(world.light(3, 8, 8).block, 13);
+        assert_eq!(world.light(4, 8, 8).block, 0);
+        assert_eq!(world.light(5, 8, 8).block, 0);
+        assert_matches_relight(&world, &registry);
+    }
+
+    #[test]
+    fn block_light_crosses_into_the_next_chunk() {
+        let (registry, TestBlocks { stone, lamp, .. }) = testing::registry();
+        let mut world = roofed_pair(&registry, stone);
+        let pair = HashSet::from([ChunkPos::new(0, 0, 0), ChunkPos::new(1, 0, 0)]);
+
+        // One block from the face shared by the two chunks
+        let changed = set_block(&mut world, &registry, [14, 8, 8], lamp);
+        assert_eq!(changed, pair);
+        assert_eq!(world.light(15, 8, 8).block, 13);
+        assert_eq!(world.light(16, 8, 8).block, 12);
+        assert_eq!(world.light(20, 9, 8).block, 7);
+        assert_matches_relight(&world, &registry);
+
+        let changed = set_block(&mut world, &registry, [14, 8, 8], AIR);
+        assert_eq!(changed, pair);
+        assert_eq!(world.light(16, 8, 8), Light::default());
+        assert_matches_relight(&world, &registry);
+    }
+
+    #[test]
+    fn sky_light_through_a_hole_crosses_into_the_next_chunk() {
+        let (registry, TestBlocks { stone, .. }) = testing::registry();
+        let mut world = roofed_pair(&registry, stone);
+        let pair = HashSet::from([ChunkPos::new(0, 0, 0), ChunkPos::new(1, 0, 0)]);
+
+        // In the last column of the first chunk, lighting the second one from the side
+        let changed = set_block(&mut world, &registry, [15, 15, 8], AIR);
+        assert_eq!(changed, pair);
+        assert_eq!(world.light(15, 0, 8).sky, MAX_LIGHT);
+        assert_eq!(world.light(16, 5, 8).sky, MAX_LIGHT - 1);
+        assert_eq!(world.light(19, 5, 9).sky, MAX_LIGHT - 5);
+        assert_matches_relight(&world, &registry);
+
+        // Closing it darkens both chunks again
+        let changed = set_block(&mut world, &registry, [15, 15, 8], stone);
+        assert_eq!(changed, pair);
+        assert_eq!(world.light(15, 5, 8).sky, 0);
+        assert_eq!(world.light(16, 5, 8).sky, 0);
+        assert_matches_relight(&world, &registry);
+    }
+}
@@ -2,10 +2,26 @@ use app::App;
 use winit::event_loop::EventLoop;
 
 mod app;
+mod block;
+mod camera;
+mod chunk;
+mod cull;
 mod data;
 mod direction;
-mod face_buffers;
+mod light;
+mod lightmap;
+mod memory;
+mod outline;
+mod player;
+mod raycast;
 mod render;
+mod resources;
+mod shadow;
+mod sky;
+mod texture;
+mod timing;
+mod transparency;
+mod world;
 
 #[tracing::instrument]
 fn main() {
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+
+/// Handle to a range of a `BufferPool`, stable across the pool growing or compacting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Allocation(u64);
+
+/// One large buffer sub-allocated into ranges, replaced by a larger or compacted buffer as
+/// ranges are allocated and freed.
+#[derive(Debug)]
+pub struct BufferPool {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    alignment: u64,
+    min_capacity: u64,
+    max_capacity: u64,
+    buffer: wgpu::Buffer,
+    ranges: Ranges,
+    /// Bumped whenever the buffer is replaced or ranges move, invalidating bind groups.
+    generation: u64,
+}
+
+impl BufferPool {
+    pub fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        min_capacity: u64,
+    ) -> Self {
+        let limits = device.limits();
+        // Growing and compacting copies ranges into a new buffer
+        let usage = usage | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST;
+
+        Self {
+            label,
+            usage,
+            alignment: limits.min_storage_buffer_offset_alignment as u64,
+            min_capacity,
+            max_capacity: limits
+                .max_buffer_size
+                .min(limits.max_storage_buffer_binding_size as u64),
+            buffer: Self::create_buffer(device, label, usage, min_capacity),
+            ranges: Ranges::default(),
+            generation: 0,
+        }
+    }
+
+    fn create_buffer(
+        device: &wgpu::Device,
+        label: &str,
+        usage: wgpu::BufferUsages,
+        size: u64,
+    ) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size,
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Bytes taken by live allocations, including alignment padding.
+    pub fn used(&self) -> u64 {
+        self.ranges.used()
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.buffer.size()
+    }
+
+    /// Offset and size of an allocation in `buffer()`.
+    pub fn range(&self, allocation: Allocation) -> (u64, u64) {
+        self.ranges.get(allocation)
+    }
+
+    pub fn binding(&self, allocation: Allocation) -> wgpu::BindingResource<'_> {
+        let (offset, size) = self.range(allocation);
+        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer: &self.buffer,
+            offset,
+            size: wgpu::BufferSize::new(size),
+        })
+    }
+
+    pub fn slice(&self, allocation: Allocation) -> wgpu::BufferSlice<'_> {
+        let (offset, size) = self.range(allocation);
+        self.buffer.slice(offset..offset + size)
+    }
+
+    /// Reserves at least `size` bytes, growing the pool if no gap is large enough. Returns
+    /// `None` if the pool would outgrow the device limits.
+    pub fn allocate(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: u64,
+    ) -> Option<Allocation> {
+        // Bindings can't be empty
+        let size = size.max(1).next_multiple_of(self.alignment);
+
+        let offset = match self.ranges.find_gap(size, self.capacity()) {
+            Some(offset) => offset,
+            None => {
+                let needed = self.used() + size;
+                let capacity = grown_capacity(self.capacity(), needed, self.max_capacity)?;
+                self.relocate(device, queue, capacity);
+                self.used()
+            }
+        };
+        Some(self.ranges.insert(offset, size))
+    }
+
+    /// Releases an allocation, shrinking the pool once it is mostly empty.
+    pub fn free(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, allocation: Allocation) {
+        self.ranges.remove(allocation);
+
+        if let Some(capacity) = shrunk_capacity(self.capacity(), self.used(), self.min_capacity) {
+            self.relocate(device, queue, capacity);
+        }
+    }
+
+    /// Moves every allocation into a new buffer of `capacity` bytes, packed from the start.
+    fn relocate(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, capacity: u64) {
+        tracing::debug!(
+            "Relocating {}: {} -> {} bytes",
+            self.label,
+            self.capacity(),
+            capacity
+        );
+        let buffer = Self::create_buffer(device, self.label, self.usage, capacity);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Buffer Pool Relocation"),
+        });
+        for (from, to, size) in self.ranges.pack() {
+            encoder.copy_buffer_to_buffer(&self.buffer, from, &buffer, to, size);
+        }
+        queue.submit([encoder.finish()]);
+
+        self.buffer = buffer;
+        self.generation += 1;
+    }
+}
+
+/// Offset and size of every live allocation of a `BufferPool`, kept apart from its buffer.
+#[derive(Debug, Default)]
+struct Ranges {
+    ranges: HashMap<Allocation, (u64, u64)>,
+    next_allocation: u64,
+}
+
+impl Ranges {
+    fn used(&self) -> u64 {
+        self.ranges.values().map(|&(_, size)| size).sum()
+    }
+
+    fn get(&self, allocation: Allocation) -> (u64, u64) {
+        self.ranges[&allocation]
+    }
+
+    fn insert(&mut self, offset: u64, size: u64) -> Allocation {
+        let allocation = Allocation(self.next_allocation);
+        self.next_allocation += 1;
+        self.ranges.insert(allocation, (offset, size));
+        allocation
+    }
+
+    fn remove(&mut self, allocation: Allocation) {
+        self.ranges.remove(&allocation);
+    }
+
+    /// First offset with `size` free bytes before the next allocation or `capacity`.
+    fn find_gap(&self, size: u64, capacity: u64) -> Option<u64> {
+        let mut ranges: Vec<_> = self.ranges.values().copied().collect();
+        ranges.sort_unstable();
+
+        let mut start = 0;
+        for (offset, len) in ranges {
+            if offset - start >= size {
+                return Some(start);
+            }
+            start = offset + len;
+        }
+        (capacity - start >= size).then_some(start)
+    }
+
+    /// Packs every allocation from the start, keeping their order, and returns the copies that
+    /// move them as source offset, destination offset and size.
+    fn pack(&mut self) -> Vec<(u64, u64, u64)> {
+        let mut ranges: Vec<_> = self.ranges.values_mut().collect();
+        ranges.sort_unstable_by_key(|range| range.0);
+
+        let mut end = 0;
+        let mut copies = Vec::with_capacity(ranges.len());
+        for (offset, size) in ranges {
+            copies.push((*offset, end, *size));
+            *offset = end;
+            end += *size;
+        }
+        copies
+    }
+}
+
+/// Capacity of a pool of `capacity` bytes that has to hold `needed` bytes, at least doubling
+/// when it grows. Returns `None` if `needed` exceeds `max_capacity`.
+fn grown_capacity(capacity: u64, needed: u64, max_capacity: u64) -> Option<u64> {
+    if needed > max_capacity {
+        None
+    } else if needed <= capacity {
+        Some(capacity)
+    } else {
+        Some((capacity * 2).clamp(needed, max_capacity))
+    }
+}
+
+/// Halved capacity of a pool of `capacity` bytes once at most a quarter of it is `used`, or
+/// `None` if it should keep its size.
+fn shrunk_capacity(capacity: u64, used: u64, min_capacity: u64) -> Option<u64> {
+    (capacity > min_capacity && used * 4 <= capacity).then(|| (capacity / 2).max(min_capacity))
+}
+
+/// Pooled storage for the faces and generated vertices of every chunk.
+#[derive(Debug)]
+pub struct GpuMemory {
+    pub faces: BufferPool,
+    pub vertices: BufferPool,
+}
+
+impl GpuMemory {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            faces: BufferPool::new(device, "Face Pool", wgpu::BufferUsages::STORAGE, 1 << 20),
+            vertices: BufferPool::new(
+                device,
+                "Vertex Pool",
+                wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+                8 << 20,
+            ),
+        }
+    }
+
+    /// Changes whenever either pool moves its allocations.
+    pub fn generation(&self) -> u64 {
+        self.faces.generation() + self.vertices.generation()
+    }
+
+    pub fn used(&self) -> u64 {
+        self.faces.used() + self.vertices.used()
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.faces.capacity() + self.vertices.capacity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_first_gap_large_enough() {
+        let mut ranges = Ranges::default();
+        let a = ranges.insert(0, 16);
+        ranges.insert(16, 32);
+        let c = ranges.insert(48, 16);
+        ranges.insert(96, 32);
+
+        assert_eq!(ranges.find_gap(32, 256), Some(64));
+        assert_eq!(ranges.find_gap(33, 256), Some(128));
+        assert_eq!(ranges.find_gap(128, 256), Some(128));
+        assert_eq!(ranges.find_gap(129, 256), None);
+
+        // Freed ranges are reused, merging with the gaps next to them
+        ranges.remove(a);
+        assert_eq!(ranges.find_gap(16, 256), Some(0));
+        ranges.remove(c);
+        assert_eq!(ranges.find_gap(48, 256), Some(48));
+        assert_eq!(ranges.used(), 64);
+    }
+
+    #[test]
+    fn full_pools_have_no_gap() {
+        let mut ranges = Ranges::default();
+        assert_eq!(ranges.find_gap(64, 64), Some(0));
+        ranges.insert(0, 64);
+        assert_eq!(ranges.find_gap(1, 64), None);
+    }
+
+    #[test]
+    fn packing_closes_gaps_in_order() {
+        let mut ranges = Ranges::default();
+        let c = ranges.insert(64, 32);
+        let a = ranges.insert(0, 16);
+        let b = ranges.insert(32, 16);
+
+        assert_eq!(ranges.pack(), [(0, 0, 16), (32, 16, 16), (64, 32, 32)]);
+        assert_eq!(ranges.get(a), (0, 16));
+        assert_eq!(ranges.get(b), (16, 16));
+        assert_eq!(ranges.get(c), (32, 32));
+        assert_eq!(ranges.find_gap(1, 128), Some(64));
+    }
+
+    #[test]
+    fn grows_by_doubling_up_to_the_limit() {
+        assert_eq!(grown_capacity(1024, 800, 4096), Some(1024));
+        assert_eq!(grown_capacity(1024, 1500, 4096), Some(2048));
+        assert_eq!(grown_capacity(1024, 3000, 4096), Some(3000));
+        assert_eq!(grown_capacity(1024, 1500, 1800), Some(1800));
+        assert_eq!(grown_capacity(1024, 5000, 4096), None);
+    }
+
+    #[test]
+    fn shrinks_once_mostly_empty() {
+        assert_eq!(shrunk_capacity(4096, 1024, 1024), Some(2048));
+        assert_eq!(shrunk_capacity(4096, 1025, 1024), None);
+        assert_eq!(shrunk_capacity(4096, 0, 3000), Some(3000));
+        assert_eq!(shrunk_capacity(1024, 0, 1024), None);
+    }
+}
@@ -0,0 +1,128 @@
+use std::num::NonZero;
+
+use crate::{
+    camera::CameraBinding,
+    render::{DEPTH_FORMAT, create_shader_module},
+};
+
+/// Edges of a cube, as a line list of corner pairs.
+const OUTLINE_VERTICES: u32 = 24;
+
+/// Wireframe outline of the block targeted for editing, drawn in the composite pass once the
+/// transparent faces are blended over the opaque ones.
+#[derive(Debug)]
+pub struct Outline {
+    /// World position of the outlined block, or `None` to draw nothing.
+    target: Option<[i32; 3]>,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Outline {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Outline Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZero::new(std::mem::size_of::<[f32; 4]>() as u64),
+                },
+                count: None,
+            }],
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Outline Buffer"),
+            // Padded to the 16 byte alignment of a uniform vec3
+            size: std::mem::size_of::<[f32; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Outline Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        let shader =
+            create_shader_module(device, "outline.wgsl", &[include_str!("./outline.wgsl")]);
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Outline Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Outline Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            // Hidden behind other blocks, but leaves the depth buffer alone
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            target: None,
+            buffer,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    /// Moves the outline to the block at `target`, or hides it.
+    pub fn update(&mut self, queue: &wgpu::Queue, target: Option<[i32; 3]>) {
+        if let Some([x, y, z]) = target
+            && self.target != target
+        {
+            let position = [x as f32, y as f32, z as f32, 0.0];
+            queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&position));
+        }
+        self.target = target;
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, camera: &CameraBinding) {
+        if self.target.is_none() {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera.bind_group(), &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..OUTLINE_VERTICES, 0..1);
+    }
+}
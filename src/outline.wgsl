@@ -0,0 +1,41 @@
+// Wireframe cube around the block the camera looks at
+
+struct Camera {
+    view_projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    position: vec3<f32>,
+}
+
+@group(0)
+@binding(0)
+var<uniform> camera: Camera;
+
+// World position of the outlined block
+@group(1)
+@binding(0)
+var<uniform> block_position: vec3<f32>;
+
+// Both corners of each of the twelve edges of a unit cube, with corners numbered x | y << 1 | z << 2
+const edges: array<u32, 24> = array(
+    0u, 1u, 2u, 3u, 4u, 5u, 6u, 7u, // Along x
+    0u, 2u, 1u, 3u, 4u, 6u, 5u, 7u, // Along y
+    0u, 4u, 1u, 5u, 2u, 6u, 3u, 7u, // Along z
+);
+
+// Distance in blocks the lines are pulled towards the camera so they aren't hidden by the faces
+// they lie on. This is the only bias the lines get: the pipeline leaves its depth bias at zero,
+// as a depth bias only applies to triangles.
+const DEPTH_BIAS: f32 = 0.01;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let corner = edges[index];
+    let position = block_position + vec3<f32>(f32(corner & 1), f32((corner >> 1) & 1), f32(corner >> 2));
+    let biased = position + normalize(camera.position - position) * DEPTH_BIAS;
+    return camera.view_projection * vec4<f32>(biased, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 0.0, 0.0, 0.6);
+}
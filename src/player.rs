@@ -0,0 +1,344 @@
+use glam::{IVec3, Vec3};
+
+use crate::{block::AIR, world::World};
+
+/// Half the width of the player's box along x and z, in blocks.
+const HALF_WIDTH: f32 = 0.3;
+/// Height of the player's box, in blocks.
+const HEIGHT: f32 = 1.8;
+/// Height of the eyes above the feet, in blocks.
+const EYE_HEIGHT: f32 = 1.62;
+/// Blocks per second squared.
+const GRAVITY: f32 = 32.0;
+/// Upward speed of a jump in blocks per second, enough to clear one block.
+const JUMP_SPEED: f32 = 9.0;
+/// Blocks per second.
+const WALK_SPEED: f32 = 4.3;
+/// Fastest fall in blocks per second, so a long fall can't tunnel through the ground.
+const TERMINAL_VELOCITY: f32 = 60.0;
+/// Gap left between the player and a block it runs into, so rounding errors never leave it
+/// inside the block.
+const CONTACT_GAP: f32 = 1e-4;
+/// Sweeps per step, enough to slide along a floor and two walls.
+const MAX_SWEEPS: usize = 3;
+
+/// A box walking through the world, stopped by every block that isn't air.
+#[derive(Debug, Clone)]
+pub struct Player {
+    /// Middle of the bottom of the box.
+    pub position: Vec3,
+    /// `position` before the last step, for drawing the player between steps.
+    pub previous_position: Vec3,
+    /// Blocks per second.
+    pub velocity: Vec3,
+    /// Set while standing on a block, when a jump can start.
+    pub on_ground: bool,
+}
+
+impl Player {
+    /// A player standing still with its eyes at `eye`.
+    pub fn new(eye: Vec3) -> Self {
+        let position = eye - Vec3::Y * EYE_HEIGHT;
+        Self {
+            position,
+            previous_position: position,
+            velocity: Vec3::ZERO,
+            on_ground: false,
+        }
+    }
+
+    /// Eye position `alpha` of the way from before the last step to after it.
+    pub fn interpolated_eye(&self, alpha: f32) -> Vec3 {
+        self.previous_position.lerp(self.position, alpha) + Vec3::Y * EYE_HEIGHT
+    }
+
+    /// Minimum and maximum corners of the player's box.
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        let half = Vec3::new(HALF_WIDTH, 0.0, HALF_WIDTH);
+        (
+            self.position - half,
+            self.position + half + Vec3::Y * HEIGHT,
+        )
+    }
+
+    /// Whether the player's box overlaps the block at `pos`.
+    pub fn intersects(&self, pos: [i32; 3]) -> bool {
+        let (min, max) = self.bounds();
+        let block = IVec3::from(pos).as_vec3();
+        min.cmplt(block + 1.0).all() && max.cmpgt(block).all()
+    }
+
+    /// Advances the player by `dt` seconds, walking towards the horizontal `direction` and
+    /// jumping if `jump` is set while on the ground. The box is swept through the world and
+    /// slides along the blocks it hits.
+    pub fn step(&mut self, world: &World, direction: Vec3, jump: bool, dt: f32) {
+        self.previous_position = self.position;
+        let walk = direction.with_y(0.0).normalize_or_zero() * WALK_SPEED;
+        self.velocity.x = walk.x;
+        self.velocity.z = walk.z;
+        if jump && self.on_ground {
+            self.velocity.y = JUMP_SPEED;
+        }
+        self.velocity.y = (self.velocity.y - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+
+        self.on_ground = false;
+        let mut motion = self.velocity * dt;
+        for _ in 0..MAX_SWEEPS {
+            let Some((time, axis)) = self.sweep(world, motion) else {
+                self.position += motion;
+                break;
+            };
+
+            // Stop short of the block, then slide along it with the rest of the motion
+            let mut advance = motion * time;
+            advance[axis] -= motion[axis].signum() * CONTACT_GAP;
+            if advance[axis].signum() != motion[axis].signum() {
+                advance[axis] = 0.0;
+            }
+            self.position += advance;
+
+            if axis == 1 && motion.y < 0.0 {
+                self.on_ground = true;
+            }
+            self.velocity[axis] = 0.0;
+            motion *= 1.0 - time;
+            motion[axis] = 0.0;
+        }
+    }
+
+    /// The fraction of `motion` the player's box can move before it hits a block, and the axis
+    /// it hits along, or `None` if it can move all the way.
+    fn sweep(&self, world: &World, motion: Vec3) -> Option<(f32, usize)> {
+        let (min, max) = self.bounds();
+        // Every block the box could touch on the way
+        let start = min.min(min + motion).floor().as_ivec3();
+        let end = max.max(max + motion).ceil().as_ivec3();
+
+        let mut hit: Option<(f32, usize)> = None;
+        for z in start.z..end.z {
+            for y in start.y..end.y {
+                for x in start.x..end.x {
+                    if world.get(x, y, z) == AIR {
+                        continue;
+                    }
+                    let block = IVec3::new(x, y, z).as_vec3();
+                    if let Some((time, axis)) = sweep_block(min, max, motion, block)
+                        && hit.is_none_or(|(nearest, _)| time < nearest)
+                    {
+                        hit = Some((time, axis));
+                    }
+                }
+            }
+        }
+        hit
+    }
+}
+
+/// When the box from `min` to `max` moving by `motion` starts touching the block at `block`, as
+/// a fraction of `motion`, and the axis it touches along. Overlaps within `CONTACT_GAP` count as
+/// touching, while boxes further inside the block are let out of it.
+fn sweep_block(min: Vec3, max: Vec3, motion: Vec3, block: Vec3) -> Option<(f32, usize)> {
+    let mut entry = (f32::NEG_INFINITY, 0);
+    let mut exit = f32::INFINITY;
+    for axis in 0..3 {
+        // Distances along the motion to where the box starts and stops overlapping the block
+        let (near, far) = if motion[axis] > 0.0 {
+            (block[axis] - max[axis], block[axis] + 1.0 - min[axis])
+        } else if motion[axis] < 0.0 {
+            (min[axis] - block[axis] - 1.0, max[axis] - block[axis])
+        } else if max[axis] > block[axis] + CONTACT_GAP
+            && min[axis] < block[axis] + 1.0 - CONTACT_GAP
+        {
+            // Not moving along this axis, but overlapping the block on it
+            continue;
+        } else {
+            return None;
+        };
+        let near = if near > -CONTACT_GAP {
+            near.max(0.0)
+        } else {
+            near
+        };
+        let speed = motion[axis].abs();
+        let (near, far) = (near / speed, far / speed);
+        if near > entry.0 {
+            entry = (near, axis);
+        }
+        exit = exit.min(far);
+    }
+
+    let (time, axis) = entry;
+    (time < exit && (0.0..=1.0).contains(&time)).then_some((time, axis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: crate::block::BlockId = 1;
+    const DT: f32 = 1.0 / 60.0;
+
+    /// A stone floor whose top is at y = 1, with walls wherever `wall` is set.
+    fn world(wall: impl Fn(i32, i32, i32) -> bool) -> World {
+        let mut world = World::default();
+        for z in -8..8 {
+            for y in 0..6 {
+                for x in -8..8 {
+                    if y == 0 || wall(x, y, z) {
+                        world.set(x, y, z, STONE);
+                    }
+                }
+            }
+        }
+        world
+    }
+
+    /// A player standing still with its feet at `position`.
+    fn player(position: Vec3) -> Player {
+        Player {
+            position,
+            previous_position: position,
+            velocity: Vec3::ZERO,
+            on_ground: false,
+        }
+    }
+
+    /// Asserts that the player's box overlaps no block.
+    fn assert_clear(world: &World, player: &Player) {
+        let (min, max) = player.bounds();
+        let (min, max) = (min.floor().as_ivec3(), max.ceil().as_ivec3());
+        for z in min.z..max.z {
+            for y in min.y..max.y {
+                for x in min.x..max.x {
+                    assert!(
+                        world.get(x, y, z) == AIR || !player.intersects([x, y, z]),
+                        "{:?} is inside the block at {:?}",
+                        player.position,
+                        [x, y, z]
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lands_on_the_ground() {
+        let world = world(|_, _, _| false);
+        let mut player = player(Vec3::new(0.5, 4.0, 0.5));
+
+        for _ in 0..60 {
+            player.step(&world, Vec3::ZERO, false, DT);
+            assert_clear(&world, &player);
+        }
+        assert!(player.on_ground);
+        assert_eq!(player.velocity, Vec3::ZERO);
+        assert!((player.position.y - 1.0).abs() < 2.0 * CONTACT_GAP);
+    }
+
+    #[test]
+    fn stays_on_the_ground_while_standing() {
+        let world = world(|_, _, _| false);
+        let mut player = player(Vec3::new(0.5, 1.0, 0.5));
+
+        for _ in 0..600 {
+            player.step(&world, Vec3::ZERO, false, DT);
+            assert!(player.on_ground);
+        }
+        assert!((player.position.y - 1.0).abs() < 2.0 * CONTACT_GAP);
+    }
+
+    #[test]
+    fn slides_along_a_wall() {
+        let world = world(|x, _, _| x == 2);
+        let mut player = player(Vec3::new(0.5, 1.0, 0.5));
+
+        for _ in 0..60 {
+            player.step(&world, Vec3::new(1.0, 0.0, 1.0), false, DT);
+            assert_clear(&world, &player);
+        }
+        // Stopped at the wall, but still walking along it at the speed left along z
+        assert!((player.position.x - (2.0 - HALF_WIDTH)).abs() < 2.0 * CONTACT_GAP);
+        let expected_z = 0.5 + WALK_SPEED / 2f32.sqrt();
+        assert!(
+            (player.position.z - expected_z).abs() < 0.01,
+            "{}",
+            player.position.z
+        );
+        assert!(player.on_ground);
+    }
+
+    #[test]
+    fn jumps_onto_a_step() {
+        let world = world(|x, y, _| x >= 2 && y == 1);
+        let mut player = player(Vec3::new(0.5, 1.0, 0.5));
+
+        for _ in 0..60 {
+            // Jumping only once close to the step
+            let jump = (1.0..2.0).contains(&player.position.x);
+            player.step(&world, Vec3::X, jump, DT);
+            assert_clear(&world, &player);
+        }
+        assert!(player.on_ground);
+        assert!((player.position.y - 2.0).abs() < 2.0 * CONTACT_GAP);
+        assert!(player.position.x > 2.0);
+    }
+
+    #[test]
+    fn hits_the_ceiling() {
+        let world = world(|_, y, _| y == 3);
+        let mut player = player(Vec3::new(0.5, 1.0, 0.5));
+        player.step(&world, Vec3::ZERO, false, DT);
+
+        // Less than a jump's height above the head
+        player.step(&world, Vec3::ZERO, true, DT);
+        let mut highest = player.position.y;
+        let mut bumped = false;
+        for _ in 0..60 {
+            let rising = player.velocity.y > 0.0;
+            player.step(&world, Vec3::ZERO, false, DT);
+            assert_clear(&world, &player);
+            highest = highest.max(player.position.y);
+            // Stopped dead by the ceiling rather than slowing down under gravity
+            bumped |= rising && player.velocity.y == 0.0 && !player.on_ground;
+        }
+        assert!(bumped);
+        assert!(
+            (highest + HEIGHT - 3.0).abs() < 2.0 * CONTACT_GAP,
+            "{highest}"
+        );
+        assert!(player.on_ground);
+        assert!((player.position.y - 1.0).abs() < 2.0 * CONTACT_GAP);
+    }
+
+    #[test]
+    fn fast_falls_dont_tunnel_through_the_ground() {
+        let world = world(|_, _, _| false);
+        let mut player = player(Vec3::new(0.5, 5.0, 0.5));
+        player.velocity.y = -TERMINAL_VELOCITY;
+
+        // Falls further than the whole world in one step
+        player.step(&world, Vec3::ZERO, false, 0.5);
+        assert_clear(&world, &player);
+        assert!(player.on_ground);
+        assert!((player.position.y - 1.0).abs() < 2.0 * CONTACT_GAP);
+    }
+
+    #[test]
+    fn fast_moves_into_a_corner_stop_at_every_side() {
+        // A floor and two walls, all hit within one step, taking one of the `MAX_SWEEPS` each
+        let world = world(|x, _, z| x == 2 || z == 2);
+        let mut player = player(Vec3::new(0.5, 3.0, 0.5));
+        player.velocity.y = -TERMINAL_VELOCITY;
+
+        player.step(&world, Vec3::new(1.0, 0.0, 1.0), false, 0.5);
+        assert_clear(&world, &player);
+        assert!(player.on_ground);
+        let corner = Vec3::new(2.0 - HALF_WIDTH, 1.0, 2.0 - HALF_WIDTH);
+        assert!(
+            player.position.abs_diff_eq(corner, 2.0 * CONTACT_GAP),
+            "{:?}",
+            player.position
+        );
+        assert_eq!(player.velocity, Vec3::ZERO);
+    }
+}
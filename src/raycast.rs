@@ -0,0 +1,203 @@
+use glam::{IVec3, Vec3};
+
+use crate::{
+    block::{AIR, BlockId},
+    direction::Direction,
+    world::World,
+};
+
+/// The first block a ray runs into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    /// World position of the block.
+    pub pos: [i32; 3],
+    pub block: BlockId,
+    /// Side of the block the ray entered through, towards the block in front of it.
+    pub face: Direction,
+    /// Along the ray from its origin to where it enters the block.
+    pub distance: f32,
+}
+
+impl RaycastHit {
+    /// World position of the block in front of the hit face, where a block placed against it
+    /// goes.
+    pub fn front(&self) -> [i32; 3] {
+        let offset = self.face.offset();
+        [0, 1, 2].map(|axis| self.pos[axis] + offset[axis])
+    }
+}
+
+/// Walks the blocks along a ray from `origin` towards `direction` one boundary crossing at a
+/// time, and returns the first that isn't air within `max_distance`. The block containing
+/// `origin` is skipped, as the ray has no face to enter it through.
+pub fn raycast(
+    world: &World,
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+) -> Option<RaycastHit> {
+    let direction = direction.try_normalize()?;
+    let mut pos = origin.floor().as_ivec3();
+    let step = IVec3::from_array(direction.to_array().map(|d| d.signum() as i32));
+    // Distance along the ray between boundaries of each axis, and to the next boundary
+    let delta = direction.recip().abs();
+    let mut next = Vec3::from_array([0, 1, 2].map(|axis| {
+        if direction[axis] > 0.0 {
+            (pos[axis] as f32 + 1.0 - origin[axis]) / direction[axis]
+        } else if direction[axis] < 0.0 {
+            (pos[axis] as f32 - origin[axis]) / direction[axis]
+        } else {
+            f32::INFINITY
+        }
+    }));
+
+    loop {
+        let axis = next.min_position();
+        let distance = next[axis];
+        if distance > max_distance {
+            return None;
+        }
+        pos[axis] += step[axis];
+        next[axis] += delta[axis];
+
+        let block = world.get(pos.x, pos.y, pos.z);
+        if block != AIR {
+            let mut normal = [0; 3];
+            normal[axis] = -step[axis];
+            let face = Direction::ALL
+                .into_iter()
+                .find(|face| face.offset() == normal)
+                .expect("every axis has a direction each way");
+            return Some(RaycastHit {
+                pos: pos.to_array(),
+                block,
+                face,
+                distance,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: BlockId = 1;
+
+    fn world(blocks: &[[i32; 3]]) -> World {
+        let mut world = World::default();
+        for &[x, y, z] in blocks {
+            world.set(x, y, z, STONE);
+        }
+        world
+    }
+
+    #[test]
+    fn hits_along_each_axis() {
+        let world = world(&[
+            [0, 5, 0],
+            [0, -5, 0],
+            [-5, 0, 0],
+            [5, 0, 0],
+            [0, 0, 5],
+            [0, 0, -5],
+        ]);
+        let origin = Vec3::splat(0.5);
+
+        for face in Direction::ALL {
+            // The ray travels against the normal of the face it enters through
+            let direction = -IVec3::from(face.offset()).as_vec3();
+            let hit = raycast(&world, origin, direction, 10.0).expect("every axis has a block");
+            assert_eq!(hit.pos, (-5 * IVec3::from(face.offset())).to_array());
+            assert_eq!(hit.block, STONE);
+            assert_eq!(hit.face, face);
+            assert!((hit.distance - 4.5).abs() < 1e-5, "{}", hit.distance);
+            assert_eq!(hit.front(), (-4 * IVec3::from(face.offset())).to_array());
+        }
+    }
+
+    #[test]
+    fn hits_along_a_diagonal() {
+        let world = world(&[[3, 3, 3]]);
+        let hit = raycast(&world, Vec3::splat(0.5), Vec3::ONE, 10.0).expect("on the diagonal");
+
+        assert_eq!(hit.pos, [3, 3, 3]);
+        assert!(
+            (hit.distance - 2.5 * 3f32.sqrt()).abs() < 1e-4,
+            "{}",
+            hit.distance
+        );
+    }
+
+    #[test]
+    fn reports_the_face_the_ray_enters_through() {
+        let world = world(&[[2, 0, 0], [2, 1, 0]]);
+
+        // Mostly along x, crossing into the next block up before reaching x = 2
+        let hit = raycast(
+            &world,
+            Vec3::new(0.5, 0.9, 0.5),
+            Vec3::new(1.0, 0.2, 0.0),
+            10.0,
+        )
+        .expect("in front of the wall");
+        assert_eq!(hit.pos, [2, 1, 0]);
+        assert_eq!(hit.face, Direction::LEFT);
+        assert_eq!(hit.front(), [1, 1, 0]);
+
+        // Steep enough to reach the top of a block before its side
+        let world = self::world(&[[1, 0, 0]]);
+        let hit = raycast(
+            &world,
+            Vec3::new(0.9, 2.5, 0.5),
+            Vec3::new(1.0, -3.0, 0.0),
+            10.0,
+        )
+        .expect("below the ray");
+        assert_eq!(hit.pos, [1, 0, 0]);
+        assert_eq!(hit.face, Direction::UP);
+        assert_eq!(hit.front(), [1, 1, 0]);
+    }
+
+    #[test]
+    fn works_at_negative_coordinates() {
+        let world = world(&[[-20, -3, -7], [1, -1, 0]]);
+        let origin = Vec3::new(-15.5, -2.5, -6.5);
+        let hit = raycast(&world, origin, Vec3::NEG_X, 10.0).expect("left of the origin");
+        assert_eq!(hit.pos, [-20, -3, -7]);
+        assert_eq!(hit.face, Direction::RIGHT);
+        assert_eq!(hit.front(), [-19, -3, -7]);
+        assert!((hit.distance - 3.5).abs() < 1e-5, "{}", hit.distance);
+        assert!(raycast(&world, origin, Vec3::NEG_X, 3.0).is_none());
+
+        // Across the chunk boundary at zero, from a negative origin
+        let hit = raycast(&world, Vec3::new(-1.5, -0.5, 0.5), Vec3::X, 10.0).expect("at x = 1");
+        assert_eq!(hit.pos, [1, -1, 0]);
+        assert_eq!(hit.face, Direction::LEFT);
+        assert!((hit.distance - 2.5).abs() < 1e-5, "{}", hit.distance);
+    }
+
+    #[test]
+    fn stops_at_max_distance() {
+        let world = world(&[[4, 0, 0]]);
+        let origin = Vec3::new(0.5, 0.5, 0.5);
+
+        assert!(raycast(&world, origin, Vec3::X, 3.4).is_none());
+        let hit = raycast(&world, origin, Vec3::X, 3.5).expect("exactly in reach");
+        assert_eq!(hit.pos, [4, 0, 0]);
+        assert!(raycast(&world, origin, Vec3::NEG_X, 100.0).is_none());
+    }
+
+    #[test]
+    fn skips_the_origin_block() {
+        let world = world(&[[0, 0, 0], [0, 2, 0]]);
+        let hit = raycast(&world, Vec3::splat(0.5), Vec3::Y, 10.0).expect("above the origin");
+        assert_eq!(hit.pos, [0, 2, 0]);
+    }
+
+    #[test]
+    fn misses_without_a_direction() {
+        let world = world(&[[1, 0, 0]]);
+        assert!(raycast(&world, Vec3::splat(0.5), Vec3::ZERO, 10.0).is_none());
+    }
+}
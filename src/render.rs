@@ -1,125 +1,436 @@
-use std::{num::NonZero, sync::Arc};
+use std::{
+    num::NonZero,
+    time::{Duration, Instant},
+};
 
-use crate::{app::AppState, data::FaceData};
+use anyhow::{Context, bail};
 
-pub const VERTEX_BUFFER_SIZE: u64 = 16 * 16 * 16;
+use crate::{
+    app::AppState,
+    chunk::{FACE_LISTS, FaceLists},
+    data::FaceData,
+    direction::Direction,
+    memory::{Allocation, GpuMemory},
+    world::ChunkPos,
+};
+
+/// Bytes of the six vertices the geometry pass generates per face, each a position, tile
+/// coordinate, normal, atlas tile, ambient occlusion and light.
+pub const FACE_VERTICES_SIZE: u64 = 6 * 11 * std::mem::size_of::<f32>() as u64;
+/// Faces meshed per workgroup of the geometry pass, matching `geometry.wgsl` and `cull.wgsl`.
+pub const GEOMETRY_WORKGROUP_SIZE: u32 = 64;
+/// Byte offset of the draw arguments of the opaque faces in a face group's indirect buffer,
+/// after the dispatch arguments of the geometry pass.
+pub const DRAW_INDIRECT_OFFSET: u64 =
+    std::mem::size_of::<wgpu::util::DispatchIndirectArgs>() as u64;
+/// Byte offset of the draw arguments of the transparent faces, after those of the opaque faces.
+pub const TRANSPARENT_DRAW_INDIRECT_OFFSET: u64 =
+    DRAW_INDIRECT_OFFSET + std::mem::size_of::<wgpu::util::DrawIndirectArgs>() as u64;
+
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Creates the depth buffer of the render pass, matching the surface size.
+pub fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Depth Texture"),
+        size: wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    texture.create_view(&Default::default())
+}
+
+/// Creates a shader module from WGSL sources, prefixed with the `FaceData` accessors and face
+/// corners generated by `FaceData::wgsl` and `Direction::wgsl`.
+pub fn create_shader_module(
+    device: &wgpu::Device,
+    label: &str,
+    sources: &[&str],
+) -> wgpu::ShaderModule {
+    let source = [FaceData::wgsl(), Direction::wgsl()]
+        .into_iter()
+        .chain(sources.iter().map(|source| source.to_string()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderPath {
+    /// The geometry pass expands faces into a vertex buffer, which is then drawn.
+    Compute,
+    /// `vs_pull` builds vertices straight from the face buffer, without a vertex buffer.
+    VertexPulling,
+}
 
 pub trait Render {
     fn render(&mut self);
 }
 
+/// Layouts of the bind groups of every `FaceGroup`.
+#[derive(Debug, Clone)]
+pub struct FaceGroupLayouts {
+    /// Face offsets, faces and chunk origin, read by the geometry pass and the render passes.
+    pub faces: wgpu::BindGroupLayout,
+    /// Vertices written by the geometry pass. A render pass can't read a buffer as vertices
+    /// while it is also bound for writing, so they get a bind group of their own.
+    pub vertices: wgpu::BindGroupLayout,
+}
+
+impl FaceGroupLayouts {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            faces: Self::create_faces_layout(device),
+            vertices: Self::create_vertices_layout(device),
+        }
+    }
+
+    fn create_faces_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Face Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(
+                            NonZero::new(
+                                std::mem::size_of::<u32>() as u64 * (FACE_LISTS + 1) as u64,
+                            )
+                            .expect("size_of::<u32>() > 0"),
+                        ),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(
+                            NonZero::new(std::mem::size_of::<i32>() as u64 * 4)
+                                .expect("size_of::<i32>() > 0"),
+                        ),
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    fn create_vertices_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Vertex Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct FaceGroup {
-    pub bind_group_layout: wgpu::BindGroupLayout,
-    vertex_buffer: Arc<wgpu::Buffer>,
+    layouts: FaceGroupLayouts,
+    offsets_buffer: wgpu::Buffer,
+    indirect_buffer: wgpu::Buffer,
+    origin_buffer: wgpu::Buffer,
+    faces: Allocation,
+    vertices: Allocation,
+    /// Faces that fit into the allocations.
+    capacity: u32,
+    path: RenderPath,
     bind_group: Option<wgpu::BindGroup>,
-    face_data: [Vec<FaceData>; 6],
-    invalidated: bool,
+    /// Binds the vertices for the geometry pass to write, kept apart from `bind_group` so the
+    /// render passes can read them as a vertex buffer.
+    vertex_bind_group: Option<wgpu::BindGroup>,
+    /// `GpuMemory::generation` the bind groups were created at.
+    generation: u64,
+    /// Set when the faces changed or moved since the geometry pass last turned them into
+    /// vertices.
+    pub dirty: bool,
 }
 
 impl FaceGroup {
-    pub fn new(device: &wgpu::Device, vertex_buffer: Arc<wgpu::Buffer>) -> Self {
-        Self {
-            bind_group_layout: device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Face Group Layout"),
+    /// Creates an empty face group with room for `capacity` faces, offset by `origin` in world
+    /// space. Vertex storage is only allocated for the compute render path. Fails if the pools
+    /// can't grow to fit it.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layouts: &FaceGroupLayouts,
+        memory: &mut GpuMemory,
+        origin: [i32; 3],
+        capacity: u32,
+        path: RenderPath,
+    ) -> anyhow::Result<Self> {
+        use wgpu::util::DeviceExt;
+        let offsets_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Face Offset Buffer"),
+            contents: bytemuck::cast_slice(&[0u32; FACE_LISTS + 1]),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let indirect_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Face Indirect Buffer"),
+            contents: &Self::indirect_args(0, 0),
+            usage: wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST,
+        });
+        let origin_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Face Origin Buffer"),
+            // Padded to the 16 byte alignment of a uniform vec3
+            contents: bytemuck::cast_slice(&[origin[0], origin[1], origin[2], 0]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let (faces, vertices) = Self::allocate(device, queue, memory, capacity, path)?;
+
+        Ok(Self {
+            layouts: layouts.clone(),
+            offsets_buffer,
+            indirect_buffer,
+            origin_buffer,
+            faces,
+            vertices,
+            capacity,
+            path,
+            bind_group: None,
+            vertex_bind_group: None,
+            generation: 0,
+            dirty: true,
+        })
+    }
+
+    fn allocate(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        memory: &mut GpuMemory,
+        capacity: u32,
+        path: RenderPath,
+    ) -> anyhow::Result<(Allocation, Allocation)> {
+        let vertices_size = match path {
+            RenderPath::Compute => capacity as u64 * FACE_VERTICES_SIZE,
+            RenderPath::VertexPulling => 0,
+        };
+        let faces = memory
+            .faces
+            .allocate(
+                device,
+                queue,
+                capacity as u64 * std::mem::size_of::<FaceData>() as u64,
+            )
+            .context("face pool exceeds the device limits")?;
+        let Some(vertices) = memory.vertices.allocate(device, queue, vertices_size) else {
+            memory.faces.free(device, queue, faces);
+            bail!("vertex pool exceeds the device limits");
+        };
+        Ok((faces, vertices))
+    }
+
+    /// Returns the face group's storage to the pools.
+    pub fn release(self, device: &wgpu::Device, queue: &wgpu::Queue, memory: &mut GpuMemory) {
+        memory.faces.free(device, queue, self.faces);
+        memory.vertices.free(device, queue, self.vertices);
+    }
+
+    /// Dispatch arguments for the geometry pass followed by draw arguments for the opaque and
+    /// then the transparent part of its output, laid out like `IndirectArgs` in `cull.wgsl`.
+    fn indirect_args(opaque_count: u32, transparent_count: u32) -> Vec<u8> {
+        [
+            wgpu::util::DispatchIndirectArgs {
+                x: (opaque_count + transparent_count).div_ceil(GEOMETRY_WORKGROUP_SIZE),
+                y: 1,
+                z: 1,
+            }
+            .as_bytes(),
+            wgpu::util::DrawIndirectArgs {
+                vertex_count: opaque_count * 6,
+                instance_count: 1,
+                first_vertex: 0,
+                first_instance: 0,
+            }
+            .as_bytes(),
+            wgpu::util::DrawIndirectArgs {
+                vertex_count: transparent_count * 6,
+                instance_count: 1,
+                first_vertex: opaque_count * 6,
+                first_instance: 0,
+            }
+            .as_bytes(),
+        ]
+        .concat()
+    }
+
+    /// Start of each of the `FACE_LISTS` in the face buffer, followed by the total face count.
+    pub fn offsets_buffer(&self) -> &wgpu::Buffer {
+        &self.offsets_buffer
+    }
+
+    /// Geometry pass dispatch arguments, followed by draw arguments at `DRAW_INDIRECT_OFFSET` and
+    /// `TRANSPARENT_DRAW_INDIRECT_OFFSET`.
+    pub fn indirect_buffer(&self) -> &wgpu::Buffer {
+        &self.indirect_buffer
+    }
+
+    /// Range of `GpuMemory::faces` holding this group's faces.
+    pub fn faces(&self) -> Allocation {
+        self.faces
+    }
+
+    pub fn vertex_slice<'a>(&self, memory: &'a GpuMemory) -> wgpu::BufferSlice<'a> {
+        memory.vertices.slice(self.vertices)
+    }
+
+    /// Drops the bind groups if the pools moved their allocations since they were created.
+    fn check_generation(&mut self, memory: &GpuMemory) {
+        if self.generation != memory.generation() {
+            self.generation = memory.generation();
+            self.bind_group = None;
+            self.vertex_bind_group = None;
+            self.dirty = true;
+        }
+    }
+
+    pub fn bind_group(&mut self, device: &wgpu::Device, memory: &GpuMemory) -> &wgpu::BindGroup {
+        self.check_generation(memory);
+        if self.bind_group.is_none() {
+            self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Face Bind Group"),
+                layout: &self.layouts.faces,
                 entries: &[
-                    wgpu::BindGroupLayoutEntry {
+                    wgpu::BindGroupEntry {
                         binding: 0,
-                        visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Storage { read_only: true },
-                            has_dynamic_offset: false,
-                            min_binding_size: Some(
-                                NonZero::new(std::mem::size_of::<u32>() as u64 * 7)
-                                    .expect("size_of::<u32>() > 0"),
-                            ),
-                        },
-                        count: None,
+                        resource: self.offsets_buffer.as_entire_binding(),
                     },
-                    wgpu::BindGroupLayoutEntry {
+                    wgpu::BindGroupEntry {
                         binding: 1,
-                        visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Storage { read_only: true },
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
+                        resource: memory.faces.binding(self.faces),
                     },
-                    wgpu::BindGroupLayoutEntry {
+                    wgpu::BindGroupEntry {
                         binding: 2,
-                        visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Storage { read_only: false },
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
+                        resource: self.origin_buffer.as_entire_binding(),
                     },
                 ],
-            }),
-            vertex_buffer,
-            bind_group: Default::default(),
-            face_data: Default::default(),
-            invalidated: true,
+            }));
         }
+        self.bind_group
+            .as_ref()
+            .expect("bind group is created above")
     }
 
-    fn bind_group(&mut self, device: &wgpu::Device) -> &wgpu::BindGroup {
-        if self.invalidated {
-            use wgpu::util::DeviceExt;
-            self.invalidated = false;
-            self.bind_group = Some(
-                device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    label: Some("Face Bind Group"),
-                    layout: &self.bind_group_layout,
-                    entries: &[
-                        wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: device
-                                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                                    label: Some("Face Offset Buffer"),
-                                    contents: bytemuck::cast_slice(
-                                        &[self.face_data.as_slice(), &[vec![FaceData::default()]]]
-                                            .concat()
-                                            .iter()
-                                            .map(|_| self.face_data.clone())
-                                            .enumerate()
-                                            .map(|(n, l)| {
-                                                l.iter().take(n + 1).map(|v| v.len() as u32).sum()
-                                            })
-                                            .collect::<Vec<u32>>(),
-                                    ),
-                                    usage: wgpu::BufferUsages::STORAGE,
-                                })
-                                .as_entire_binding(),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 1,
-                            resource: device
-                                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                                    label: Some("Face Data Buffer"),
-                                    contents: &[
-                                        bytemuck::cast_slice(&self.face_data.concat()),
-                                        // Disallowing zero length buffers
-                                        &[0],
-                                    ]
-                                    .concat(),
-                                    usage: wgpu::BufferUsages::STORAGE,
-                                })
-                                .as_entire_binding(),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 2,
-                            resource: self.vertex_buffer.as_entire_binding(),
-                        },
-                    ],
-                }),
-            )
+    /// Binds the vertices for the geometry pass to write.
+    pub fn vertex_bind_group(
+        &mut self,
+        device: &wgpu::Device,
+        memory: &GpuMemory,
+    ) -> &wgpu::BindGroup {
+        self.check_generation(memory);
+        if self.vertex_bind_group.is_none() {
+            self.vertex_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Vertex Bind Group"),
+                layout: &self.layouts.vertices,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: memory.vertices.binding(self.vertices),
+                }],
+            }));
         }
-        self.bind_group
+        self.vertex_bind_group
             .as_ref()
-            .expect("FaceGroup is invalidated at creation.")
+            .expect("bind group is created above")
+    }
+
+    /// Grows the allocations to fit `capacity` faces, keeping them if they already do. Faces
+    /// already uploaded are lost when they grow. Fails without changing the allocations if the
+    /// pools can't grow to fit them.
+    pub fn reserve(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        memory: &mut GpuMemory,
+        capacity: u32,
+    ) -> anyhow::Result<()> {
+        if capacity <= self.capacity {
+            return Ok(());
+        }
+        let (faces, vertices) = Self::allocate(device, queue, memory, capacity, self.path)?;
+        memory.faces.free(device, queue, self.faces);
+        memory.vertices.free(device, queue, self.vertices);
+        (self.faces, self.vertices) = (faces, vertices);
+        self.capacity = capacity;
+        self.bind_group = None;
+        self.vertex_bind_group = None;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Uploads faces meshed on the CPU, growing the allocations if they don't fit.
+    pub fn set_face_data(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        memory: &mut GpuMemory,
+        face_data: &FaceLists,
+    ) -> anyhow::Result<()> {
+        let mut offsets = [0u32; FACE_LISTS + 1];
+        for (n, faces) in face_data.iter().enumerate() {
+            let direction = Direction::ALL[n % Direction::ALL.len()];
+            let kind = if n < Direction::ALL.len() {
+                "opaque"
+            } else {
+                "transparent"
+            };
+            tracing::debug!("{} {kind} faces: {}", direction.name(), faces.len());
+            offsets[n + 1] = offsets[n] + faces.len() as u32;
+        }
+
+        let face_count = offsets[FACE_LISTS];
+        self.reserve(device, queue, memory, face_count)?;
+
+        let opaque_count = offsets[Direction::ALL.len()];
+        queue.write_buffer(&self.offsets_buffer, 0, bytemuck::cast_slice(&offsets));
+        queue.write_buffer(
+            &self.indirect_buffer,
+            0,
+            &Self::indirect_args(opaque_count, face_count - opaque_count),
+        );
+        let faces = face_data.concat();
+        if !faces.is_empty() {
+            let (offset, _) = memory.faces.range(self.faces);
+            queue.write_buffer(memory.faces.buffer(), offset, bytemuck::cast_slice(&faces));
+        }
+        self.dirty = true;
+        Ok(())
     }
 }
 
@@ -139,18 +450,90 @@ impl Render for AppState {
 
         let mut encoder = self.device.create_command_encoder(&Default::default());
 
-        {
+        if self.gpu_culling {
+            for (pos, cull_group) in &mut self.cull_groups {
+                if cull_group.dirty {
+                    self.cull_pipeline.cull(
+                        &self.device,
+                        &mut encoder,
+                        cull_group,
+                        self.face_groups
+                            .get_mut(pos)
+                            .expect("cull groups have a face group"),
+                        &self.memory,
+                    );
+                }
+            }
+        }
+
+        if self.render_path == RenderPath::Compute {
             let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
                 label: Some("Compute Pass"),
                 timestamp_writes: None,
             });
 
             compute_pass.set_pipeline(&self.compute_pipeline);
-            compute_pass.set_bind_group(0, self.face_group.bind_group(&self.device), &[]);
-            // TODO: Variables
-            compute_pass.dispatch_workgroups(1, 6, 1);
+            for face_group in self.face_groups.values_mut() {
+                face_group.check_generation(&self.memory);
+                if !face_group.dirty {
+                    continue;
+                }
+                compute_pass.set_bind_group(
+                    0,
+                    face_group.bind_group(&self.device, &self.memory),
+                    &[],
+                );
+                compute_pass.set_bind_group(
+                    1,
+                    face_group.vertex_bind_group(&self.device, &self.memory),
+                    &[],
+                );
+                compute_pass.dispatch_workgroups_indirect(face_group.indirect_buffer(), 0);
+                face_group.dirty = false;
+            }
         }
 
+        if self.shadow_map.enabled {
+            for (cascade_view, cascade_bind_group) in self.shadow_map.cascades() {
+                let mut shadow_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Shadow Pass"),
+                    color_attachments: &[],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: cascade_view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Store,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+
+                shadow_pass.set_pipeline(self.shadow_map.pipeline(self.render_path));
+                shadow_pass.set_bind_group(1, cascade_bind_group, &[]);
+                for face_group in self.face_groups.values_mut() {
+                    shadow_pass.set_bind_group(
+                        0,
+                        face_group.bind_group(&self.device, &self.memory),
+                        &[],
+                    );
+                    // The face group binds nothing writable, so the geometry pass output can be read
+                    // as vertices in the same pass
+                    if self.render_path == RenderPath::Compute {
+                        shadow_pass.set_vertex_buffer(0, face_group.vertex_slice(&self.memory));
+                    }
+                    // Only opaque faces, transparent blocks let the sun through
+                    shadow_pass.draw_indirect(face_group.indirect_buffer(), DRAW_INDIRECT_OFFSET);
+                }
+            }
+        }
+
+        let (opaque_pipeline, transparent_pipeline) = match self.render_path {
+            RenderPath::Compute => (&self.render_pipeline, &self.transparent_pipeline),
+            RenderPath::VertexPulling => (&self.pull_pipeline, &self.transparent_pull_pipeline),
+        };
+
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
@@ -158,28 +541,135 @@ impl Render for AppState {
                     view: &texture_view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
+                        // Covered by the sky
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
                 timestamp_writes: None,
                 occlusion_query_set: None,
             });
 
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            // TODO: Variables
-            render_pass.draw(0..6, 0..1);
+            self.sky.draw(
+                &mut render_pass,
+                &self.camera_binding,
+                &self.lighting_binding,
+            );
+
+            // From the nearest chunk, to skip shading what they hide
+            let mut positions: Vec<ChunkPos> = self.face_groups.keys().copied().collect();
+            positions.sort_by(|a, b| {
+                let distance = |pos: &ChunkPos| self.camera.position.distance_squared(pos.center());
+                distance(a).total_cmp(&distance(b))
+            });
+
+            render_pass.set_pipeline(opaque_pipeline);
+            render_pass.set_bind_group(1, self.camera_binding.bind_group(), &[]);
+            render_pass.set_bind_group(2, self.atlas.bind_group(), &[]);
+            render_pass.set_bind_group(3, self.lighting_binding.bind_group(), &[]);
+            for pos in &positions {
+                let face_group = self
+                    .face_groups
+                    .get_mut(pos)
+                    .expect("positions are taken from the face groups");
+                render_pass.set_bind_group(
+                    0,
+                    face_group.bind_group(&self.device, &self.memory),
+                    &[],
+                );
+                if self.render_path == RenderPath::Compute {
+                    render_pass.set_vertex_buffer(0, face_group.vertex_slice(&self.memory));
+                }
+                render_pass.draw_indirect(face_group.indirect_buffer(), DRAW_INDIRECT_OFFSET);
+            }
+        }
+
+        {
+            // Tested against the opaque faces, but blended in any order
+            let mut transparent_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Transparent Pass"),
+                color_attachments: &self.transparency.color_attachments(),
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            transparent_pass.set_pipeline(transparent_pipeline);
+            transparent_pass.set_bind_group(1, self.camera_binding.bind_group(), &[]);
+            transparent_pass.set_bind_group(2, self.atlas.bind_group(), &[]);
+            transparent_pass.set_bind_group(3, self.lighting_binding.bind_group(), &[]);
+            for face_group in self.face_groups.values_mut() {
+                transparent_pass.set_bind_group(
+                    0,
+                    face_group.bind_group(&self.device, &self.memory),
+                    &[],
+                );
+                if self.render_path == RenderPath::Compute {
+                    transparent_pass.set_vertex_buffer(0, face_group.vertex_slice(&self.memory));
+                }
+                transparent_pass.draw_indirect(
+                    face_group.indirect_buffer(),
+                    TRANSPARENT_DRAW_INDIRECT_OFFSET,
+                );
+            }
+        }
+
+        {
+            let mut composite_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Composite Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &texture_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            self.transparency.composite(&mut composite_pass);
+            self.outline.draw(&mut composite_pass, &self.camera_binding);
         }
 
         self.queue.submit([encoder.finish()]);
         self.window.pre_present_notify();
         surface_texture.present();
+
+        self.frame_count += 1;
+        let elapsed = self.frame_timer.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            tracing::info!(
+                "{:?}: {:.2} ms/frame",
+                self.render_path,
+                elapsed.as_secs_f64() * 1000.0 / self.frame_count as f64
+            );
+            self.frame_count = 0;
+            self.frame_timer = Instant::now();
+        }
     }
 }
@@ -0,0 +1,339 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail, ensure};
+use image::RgbaImage;
+use serde::Deserialize;
+
+use crate::{
+    block::{Block, BlockRegistry, BlockTextures, TextureCoord},
+    direction::Direction,
+    lightmap::MAX_LIGHT,
+    texture::TILE_SIZE,
+};
+
+/// Directory the resource pack is loaded from, relative to the working directory.
+pub const ASSETS_DIR: &str = "assets";
+
+/// A block definition file in `blocks/`, in RON, TOML or JSON.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockDefinition {
+    /// Defaults to the file name without its extension.
+    name: Option<String>,
+    #[serde(default = "default_opaque")]
+    opaque: bool,
+    #[serde(default)]
+    transparent: bool,
+    /// Block light level given off, up to `MAX_LIGHT`.
+    #[serde(default)]
+    emission: u8,
+    textures: TextureDefinition,
+}
+
+fn default_opaque() -> bool {
+    true
+}
+
+/// Texture names per face. A face takes its own entry if present, then `side` for the four
+/// horizontal faces, then `all`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TextureDefinition {
+    all: Option<String>,
+    side: Option<String>,
+    up: Option<String>,
+    down: Option<String>,
+    left: Option<String>,
+    right: Option<String>,
+    front: Option<String>,
+    back: Option<String>,
+}
+
+impl TextureDefinition {
+    fn get(&self, direction: Direction) -> Option<&str> {
+        let (face, side) = match direction {
+            Direction::UP => (&self.up, None),
+            Direction::DOWN => (&self.down, None),
+            Direction::LEFT => (&self.left, self.side.as_ref()),
+            Direction::RIGHT => (&self.right, self.side.as_ref()),
+            Direction::FRONT => (&self.front, self.side.as_ref()),
+            Direction::BACK => (&self.back, self.side.as_ref()),
+        };
+        face.as_ref()
+            .or(side)
+            .or(self.all.as_ref())
+            .map(String::as_str)
+    }
+}
+
+/// Blocks and textures loaded from an assets directory:
+///
+/// - `textures/<name>.png`: one `TILE_SIZE` square tile per file, referenced by `<name>`
+/// - `blocks/<name>.{ron,toml,json}`: one `BlockDefinition` per file
+#[derive(Debug)]
+pub struct ResourcePack {
+    pub registry: BlockRegistry,
+    /// Every texture packed into a grid, addressed by the `TextureCoord`s in `registry`.
+    pub atlas: RgbaImage,
+}
+
+impl ResourcePack {
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let (atlas, textures) = Self::load_textures(&dir.join("textures"))?;
+
+        let mut registry = BlockRegistry::default();
+        for path in Self::files(&dir.join("blocks"))? {
+            let block = Self::load_block(&path, &textures)
+                .with_context(|| format!("failed to load block {}", path.display()))?;
+            ensure!(
+                registry.id(&block.name).is_none(),
+                "block {} in {} is already defined",
+                block.name,
+                path.display()
+            );
+            registry.register(block);
+        }
+
+        tracing::info!(
+            "Loaded {} blocks and {} textures from {}",
+            registry.iter().count() - 1,
+            textures.len(),
+            dir.display()
+        );
+        Ok(Self { registry, atlas })
+    }
+
+    /// Files in `dir`, sorted so that block ids and atlas tiles don't depend on the file system.
+    fn files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in
+            fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?
+        {
+            let path = entry
+                .with_context(|| format!("failed to read {}", dir.display()))?
+                .path();
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    fn file_stem(path: &Path) -> anyhow::Result<&str> {
+        path.file_stem()
+            .and_then(|stem| stem.to_str())
+            .with_context(|| format!("{} has no UTF-8 file name", path.display()))
+    }
+
+    /// Packs the PNGs in `dir` into a square-ish grid, returning it with the tile of each.
+    fn load_textures(dir: &Path) -> anyhow::Result<(RgbaImage, HashMap<String, TextureCoord>)> {
+        let paths: Vec<_> = Self::files(dir)?
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+            .collect();
+        ensure!(!paths.is_empty(), "no textures in {}", dir.display());
+
+        let mut columns = paths.len().isqrt();
+        if columns * columns < paths.len() {
+            columns += 1;
+        }
+        let rows = paths.len().div_ceil(columns);
+        // Tiles are addressed by u8 coordinates
+        ensure!(
+            columns <= u8::MAX as usize + 1,
+            "{} textures don't fit in the atlas",
+            paths.len()
+        );
+
+        let mut atlas = RgbaImage::new(columns as u32 * TILE_SIZE, rows as u32 * TILE_SIZE);
+        let mut textures = HashMap::new();
+        for (index, path) in paths.iter().enumerate() {
+            let image = image::open(path)
+                .with_context(|| format!("failed to load texture {}", path.display()))?
+                .into_rgba8();
+            ensure!(
+                image.dimensions() == (TILE_SIZE, TILE_SIZE),
+                "texture {} is {}x{} pixels instead of {TILE_SIZE}x{TILE_SIZE}",
+                path.display(),
+                image.width(),
+                image.height()
+            );
+
+            let texture = TextureCoord::new((index % columns) as u8, (index / columns) as u8);
+            image::imageops::replace(
+                &mut atlas,
+                &image,
+                texture.u as i64 * TILE_SIZE as i64,
+                texture.v as i64 * TILE_SIZE as i64,
+            );
+            textures.insert(Self::file_stem(path)?.to_owned(), texture);
+        }
+
+        Ok((atlas, textures))
+    }
+
+    fn load_block(path: &Path, textures: &HashMap<String, TextureCoord>) -> anyhow::Result<Block> {
+        let source = fs::read_to_string(path)?;
+        let definition: BlockDefinition = match path.extension().and_then(|e| e.to_str()) {
+            // Optional fields take plain values, like in the other formats
+            Some("ron") => ron::Options::default()
+                .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+                .from_str(&source)?,
+            Some("toml") => toml::from_str(&source)?,
+            Some("json") => serde_json::from_str(&source)?,
+            _ => bail!("unknown block definition format, expected .ron, .toml or .json"),
+        };
+
+        let name = match definition.name {
+            Some(name) => name,
+            None => Self::file_stem(path)?.to_owned(),
+        };
+        ensure!(name != "air", "air is built in and can't be redefined");
+        ensure!(
+            definition.emission <= MAX_LIGHT,
+            "emission {} is above the maximum light level of {MAX_LIGHT}",
+            definition.emission
+        );
+
+        let mut faces = [TextureCoord::default(); 6];
+        for direction in Direction::ALL {
+            let texture = definition
+                .textures
+                .get(direction)
+                .with_context(|| format!("no texture for the {} face", direction.name()))?;
+            faces[direction as usize] = *textures
+                .get(texture)
+                .with_context(|| format!("unknown texture {texture}"))?;
+        }
+
+        let mut block = Block::new(&name, BlockTextures::new(faces));
+        block.opaque = definition.opaque;
+        block.transparent = definition.transparent;
+        block.emission = definition.emission;
+        Ok(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: &str = r#"textures = { all = "stone" }"#;
+
+    /// A pack with a single `stone` texture and the given block files, in a fresh temporary
+    /// directory removed when dropped.
+    struct TempPack(PathBuf);
+
+    impl TempPack {
+        fn new(name: &str, blocks: &[(&str, &str)]) -> Self {
+            let dir = std::env::temp_dir().join(format!("golem-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(dir.join("textures")).unwrap();
+            fs::create_dir_all(dir.join("blocks")).unwrap();
+            RgbaImage::new(TILE_SIZE, TILE_SIZE)
+                .save(dir.join("textures/stone.png"))
+                .unwrap();
+            for (file, source) in blocks {
+                fs::write(dir.join("blocks").join(file), source).unwrap();
+            }
+            Self(dir)
+        }
+
+        /// The error loading the pack fails with, with its causes.
+        fn error(&self) -> String {
+            let err = ResourcePack::load(&self.0).expect_err("the pack is invalid");
+            format!("{err:#}")
+        }
+    }
+
+    impl Drop for TempPack {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn assert_contains(error: String, expected: &str) {
+        assert!(error.contains(expected), "{error:?} lacks {expected:?}");
+    }
+
+    #[test]
+    fn loads_blocks_in_every_format() {
+        let pack = TempPack::new(
+            "formats",
+            &[
+                ("stone.toml", STONE),
+                ("cobble.json", r#"{ "textures": { "all": "stone" } }"#),
+                ("lamp.ron", r#"(emission: 15, textures: (all: "stone"))"#),
+            ],
+        );
+        let pack = ResourcePack::load(&pack.0).unwrap();
+        for name in ["stone", "cobble", "lamp"] {
+            assert!(pack.registry.id(name).is_some(), "{name} is missing");
+        }
+    }
+
+    #[test]
+    fn unknown_textures_are_rejected() {
+        let pack = TempPack::new(
+            "texture",
+            &[("dirt.toml", r#"textures = { all = "dirt" }"#)],
+        );
+        assert_contains(pack.error(), "dirt.toml");
+        assert_contains(pack.error(), "unknown texture dirt");
+    }
+
+    #[test]
+    fn every_face_needs_a_texture() {
+        let pack = TempPack::new(
+            "face",
+            &[("log.toml", r#"textures = { side = "stone", up = "stone" }"#)],
+        );
+        assert_contains(pack.error(), "no texture for the Down face");
+    }
+
+    #[test]
+    fn unsupported_formats_are_rejected() {
+        let pack = TempPack::new("format", &[("stone.yaml", "textures: { all: stone }")]);
+        assert_contains(pack.error(), "stone.yaml");
+        assert_contains(pack.error(), "unknown block definition format");
+    }
+
+    #[test]
+    fn emission_is_capped_at_the_maximum_light_level() {
+        let pack = TempPack::new(
+            "emission",
+            &[("lamp.toml", "emission = 16\ntextures = { all = \"stone\" }")],
+        );
+        assert_contains(
+            pack.error(),
+            "emission 16 is above the maximum light level of 15",
+        );
+    }
+
+    #[test]
+    fn air_cant_be_redefined() {
+        let pack = TempPack::new("air", &[("air.toml", STONE)]);
+        assert_contains(pack.error(), "air is built in and can't be redefined");
+    }
+
+    #[test]
+    fn block_names_are_unique() {
+        let pack = TempPack::new(
+            "duplicate",
+            &[
+                (
+                    "cobble.toml",
+                    "name = \"stone\"\ntextures = { all = \"stone\" }",
+                ),
+                ("stone.toml", STONE),
+            ],
+        );
+        assert_contains(pack.error(), "block stone in");
+        assert_contains(pack.error(), "stone.toml is already defined");
+    }
+}
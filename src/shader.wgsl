@@ -1,21 +1,178 @@
+struct Camera {
+    view_projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    position: vec3<f32>,
+}
+
+@group(1)
+@binding(0)
+var<uniform> camera: Camera;
+
+struct Atlas {
+    columns: u32,
+}
+
+// One tile per layer
+@group(2)
+@binding(0)
+var atlas_texture: texture_2d_array<f32>;
+
+@group(2)
+@binding(1)
+var atlas_sampler: sampler;
+
+@group(2)
+@binding(2)
+var<uniform> atlas: Atlas;
+
+@group(3)
+@binding(0)
+var<uniform> lighting: Lighting;
+
+// Matches `CASCADES` in shadow.rs
+const CASCADES: u32 = 3;
+
+struct Shadows {
+    // Light view and projection of each cascade, nearest first
+    cascades: array<mat4x4<f32>, CASCADES>,
+    enabled: u32,
+    // Of the shadow map, in texture coordinates
+    texel_size: f32,
+}
+
+// One cascade per layer
+@group(3)
+@binding(1)
+var shadow_map: texture_depth_2d_array;
+
+@group(3)
+@binding(2)
+var shadow_sampler: sampler_comparison;
+
+@group(3)
+@binding(3)
+var<uniform> shadows: Shadows;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
+    @location(1) tile: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) texture: u32,
+    @location(4) ao: f32,
+    @location(5) light: u32,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) color: vec3<f32>
+    @location(0) tile: vec2<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) texture: u32,
+    @location(3) ao: f32,
+    @location(4) light: u32,
+    @location(5) world_position: vec3<f32>,
 };
 
-@vertex
-fn vs_main(model: VertexInput) -> VertexOutput {
+fn vertex(position: vec3<f32>, tile: vec2<f32>, normal: vec3<f32>, texture: u32, ao: f32, light: u32) -> VertexOutput {
     var out: VertexOutput;
-    out.color = (model.position + vec3<f32>(1.0, 1.0, 1.0)) / 2;
-    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.ao = ao;
+    out.light = light;
+    out.normal = normal;
+    out.tile = tile;
+    out.texture = texture;
+    out.world_position = position;
+    out.clip_position = camera.view_projection * vec4<f32>(position, 1.0);
     return out;
 }
 
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    return vertex(model.position, model.tile, model.normal, model.texture, model.ao, model.light);
+}
+
+// Builds the vertices straight from the face buffer, without a geometry pass
+@vertex
+fn vs_pull(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let face = index / 6;
+    let vertices = face_vertices(face, face_direction(face));
+    let corner = quad_corner(vertices, index % 6);
+    return vertex(vertices.positions[corner], vertices.tiles[corner], vertices.normal, vertices.texture, vertices.ao[corner], vertices.light);
+}
+
+// Brightness of a flood-filled light level, losing a fifth per level below the maximum of 15
+fn light_level(level: u32) -> f32 {
+    return pow(0.8, f32(15 - level));
+}
+
+// Fraction of sunlight reaching a point of a face, from the nearest cascade covering it,
+// averaged over 3x3 texels to soften the shadow edges
+fn sunlight(position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    if shadows.enabled == 0u {
+        return 1.0;
+    }
+
+    // Nudged off the face so it doesn't shadow itself where the depth is rounded
+    let offset_position = vec4<f32>(position + normal * 0.05, 1.0);
+    for (var cascade = 0u; cascade < CASCADES; cascade++) {
+        let clip = shadows.cascades[cascade] * offset_position;
+        let uv = clip.xy * vec2<f32>(0.5, -0.5) + 0.5;
+        if all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0)) && clip.z <= 1.0 {
+            var lit = 0.0;
+            for (var y = -1; y <= 1; y++) {
+                for (var x = -1; x <= 1; x++) {
+                    let texel = uv + vec2<f32>(f32(x), f32(y)) * shadows.texel_size;
+                    lit += textureSampleCompareLevel(shadow_map, shadow_sampler, texel, cascade, clip.z);
+                }
+            }
+            return lit / 9.0;
+        }
+    }
+    return 1.0;
+}
+
+// Lit and fogged color of a face, with the texture's alpha
+fn shade(in: VertexOutput) -> vec4<f32> {
+    let layer = (in.texture & 0xff) + (in.texture >> 8) * atlas.columns;
+    // Tiles count up the faces while image rows count down, and the sampler repeats the tile
+    // across merged faces
+    let uv = vec2<f32>(in.tile.x, -in.tile.y);
+    let color = textureSample(atlas_texture, atlas_sampler, uv, layer);
+
+    let normal = normalize(in.normal);
+    let diffuse = max(dot(normal, lighting.sun_direction), 0.0) * sunlight(in.world_position, normal);
+    let sky = light_level(in.light & 0xf);
+    let block = light_level(in.light >> 4);
+    // Occluded corners go down to 40% of the light
+    let occlusion = 0.4 + 0.2 * in.ao;
+    let light = ((lighting.ambient + lighting.sun_color * diffuse) * sky + lighting.block_light_color * block) * occlusion;
+
+    // Blends into the sky behind towards the view distance
+    let view_offset = in.world_position - camera.position;
+    let fog = smoothstep(lighting.fog_start, lighting.fog_end, length(view_offset));
+    let fog_color = sky_color(lighting, normalize(view_offset));
+    return vec4<f32>(mix(color.rgb * light, fog_color, fog), color.a);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color, 1.0);
+    return shade(in);
+}
+
+// Matches `Transparency::color_targets` in transparency.rs
+struct TransparentOutput {
+    @location(0) accum: vec4<f32>,
+    @location(1) revealage: f32,
+}
+
+// Accumulates a transparent face in any order, weighted so nearer faces dominate the blend
+// (McGuire and Bavoil, "Weighted Blended Order-Independent Transparency")
+@fragment
+fn fs_transparent(in: VertexOutput) -> TransparentOutput {
+    let color = shade(in);
+    let distance = length(in.world_position - camera.position);
+    let weight = color.a * clamp(10.0 / (1e-5 + pow(distance / 5.0, 2.0) + pow(distance / 200.0, 6.0)), 1e-2, 3e3);
+
+    var out: TransparentOutput;
+    out.accum = vec4<f32>(color.rgb * color.a, color.a) * weight;
+    out.revealage = color.a;
+    return out;
 }
@@ -0,0 +1,318 @@
+use std::num::NonZero;
+
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::{
+    camera::{Camera, CameraBinding},
+    render::{DEPTH_FORMAT, FACE_VERTICES_SIZE, RenderPath, create_shader_module},
+};
+
+/// Slices the view is split into along its depth, each with its own layer of the shadow map.
+pub const CASCADES: usize = 3;
+/// Edge length of a cascade in texels.
+const SHADOW_MAP_SIZE: u32 = 2048;
+/// Distance from the camera up to which shadows are drawn.
+const SHADOW_DISTANCE: f32 = 128.0;
+/// Blend from evenly spaced (0) to logarithmically spaced (1) cascade splits, so that near
+/// cascades cover less of the view and get sharper shadows.
+const SPLIT_BLEND: f32 = 0.75;
+/// Distance beyond a cascade towards the sun that still casts shadows into it.
+const CASTER_DISTANCE: f32 = 64.0;
+
+/// Shadow data laid out as `Shadows` in `shader.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowUniform {
+    cascades: [[[f32; 4]; 4]; CASCADES],
+    enabled: u32,
+    texel_size: f32,
+    _padding: [u32; 2],
+}
+
+/// Cascaded shadows of the sun: a depth-only pass per cascade from the sun's point of view,
+/// sampled with percentage-closer filtering in `fs_main`.
+#[derive(Debug)]
+pub struct ShadowMap {
+    /// Draws the shadow passes, and shadows are sampled, only while set.
+    pub enabled: bool,
+    view: wgpu::TextureView,
+    cascade_views: Vec<wgpu::TextureView>,
+    cascade_bindings: Vec<CameraBinding>,
+    sampler: wgpu::Sampler,
+    buffer: wgpu::Buffer,
+    pipeline: wgpu::RenderPipeline,
+    pull_pipeline: wgpu::RenderPipeline,
+}
+
+impl ShadowMap {
+    /// `face_group_layout` is `FaceGroupLayouts::faces`. The vertices drawn on the compute path
+    /// are read as a vertex buffer, so their writable binding stays out of the shadow passes.
+    pub fn new(
+        device: &wgpu::Device,
+        face_group_layout: &wgpu::BindGroupLayout,
+        camera_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Map"),
+            size: wgpu::Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                depth_or_array_layers: CASCADES as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let cascade_views = (0..CASCADES as u32)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Shadow Cascade"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let cascade_bindings = (0..CASCADES)
+            .map(|_| CameraBinding::new(device, camera_layout))
+            .collect();
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Buffer"),
+            size: std::mem::size_of::<ShadowUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let shader = create_shader_module(
+            device,
+            "shadow.wgsl",
+            &[include_str!("./face.wgsl"), include_str!("./shadow.wgsl")],
+        );
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[face_group_layout, camera_layout],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |entry_point: &str, buffers: &[wgpu::VertexBufferLayout]| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Shadow Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: Default::default(),
+                    buffers,
+                },
+                // Only the faces turned away from the sun cast shadows, so the lit faces in
+                // front of them never shadow themselves
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Front),
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState {
+                        constant: 2,
+                        slope_scale: 2.0,
+                        clamp: 0.0,
+                    },
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                fragment: None,
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        // Only the positions of the geometry pass output
+        let pipeline = create_pipeline(
+            "vs_main",
+            &[wgpu::VertexBufferLayout {
+                array_stride: FACE_VERTICES_SIZE / 6,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &[wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x3,
+                    offset: 0,
+                    shader_location: 0,
+                }],
+            }],
+        );
+        let pull_pipeline = create_pipeline("vs_pull", &[]);
+
+        Self {
+            enabled: true,
+            view,
+            cascade_views,
+            cascade_bindings,
+            sampler,
+            buffer,
+            pipeline,
+            pull_pipeline,
+        }
+    }
+
+    /// Bindings of the shadow map in the lighting bind group, from `first_binding` on.
+    pub fn bind_group_layout_entries(first_binding: u32) -> [wgpu::BindGroupLayoutEntry; 3] {
+        [
+            wgpu::BindGroupLayoutEntry {
+                binding: first_binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: first_binding + 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: first_binding + 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZero::new(std::mem::size_of::<ShadowUniform>() as u64),
+                },
+                count: None,
+            },
+        ]
+    }
+
+    /// Resources for the entries of `bind_group_layout_entries`.
+    pub fn bind_group_entries(&self, first_binding: u32) -> [wgpu::BindGroupEntry<'_>; 3] {
+        [
+            wgpu::BindGroupEntry {
+                binding: first_binding,
+                resource: wgpu::BindingResource::TextureView(&self.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: first_binding + 1,
+                resource: wgpu::BindingResource::Sampler(&self.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: first_binding + 2,
+                resource: self.buffer.as_entire_binding(),
+            },
+        ]
+    }
+
+    pub fn pipeline(&self, render_path: RenderPath) -> &wgpu::RenderPipeline {
+        match render_path {
+            RenderPath::Compute => &self.pipeline,
+            RenderPath::VertexPulling => &self.pull_pipeline,
+        }
+    }
+
+    /// Depth attachment and light camera bind group of each cascade's shadow pass.
+    pub fn cascades(&self) -> impl Iterator<Item = (&wgpu::TextureView, &wgpu::BindGroup)> {
+        self.cascade_views
+            .iter()
+            .zip(self.cascade_bindings.iter().map(CameraBinding::bind_group))
+    }
+
+    /// Fits every cascade around its slice of the camera's view, as seen from `sun_direction`.
+    pub fn update(&self, queue: &wgpu::Queue, camera: &Camera, sun_direction: Vec3) {
+        let near = camera.near;
+        let far = camera.far.min(SHADOW_DISTANCE);
+        let split = |cascade: usize| {
+            let fraction = cascade as f32 / CASCADES as f32;
+            let even = near + (far - near) * fraction;
+            let logarithmic = near * (far / near).powf(fraction);
+            even + (logarithmic - even) * SPLIT_BLEND
+        };
+
+        let mut cascades = [[[0.0; 4]; 4]; CASCADES];
+        for (cascade, binding) in self.cascade_bindings.iter().enumerate() {
+            let view_projection =
+                Self::fit_cascade(camera, split(cascade), split(cascade + 1), sun_direction);
+            binding.update_view_projection(queue, view_projection);
+            cascades[cascade] = view_projection.to_cols_array_2d();
+        }
+
+        let uniform = ShadowUniform {
+            cascades,
+            enabled: self.enabled as u32,
+            texel_size: 1.0 / SHADOW_MAP_SIZE as f32,
+            _padding: [0; 2],
+        };
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    /// Light view and projection covering the part of the camera's view from `near` to `far`.
+    /// The cascade is sized to the bounding sphere of that slice and moved in whole texels, so
+    /// shadow edges neither swim nor shimmer as the camera turns and moves.
+    fn fit_cascade(camera: &Camera, near: f32, far: f32, sun_direction: Vec3) -> Mat4 {
+        let slice = Camera {
+            near,
+            far,
+            ..camera.clone()
+        };
+        let inverse = slice.view_projection().inverse();
+        let corners = [-1.0, 1.0].into_iter().flat_map(|x| {
+            [-1.0, 1.0].into_iter().flat_map(move |y| {
+                [0.0, 1.0]
+                    .into_iter()
+                    .map(move |z| inverse.project_point3(Vec3::new(x, y, z)))
+            })
+        });
+        let corners: Vec<Vec3> = corners.collect();
+        let center = corners.iter().sum::<Vec3>() / corners.len() as f32;
+        let radius = corners
+            .iter()
+            .map(|corner| corner.distance(center))
+            .fold(0.0, f32::max);
+        // Rounded up so the size doesn't flicker with rounding errors
+        let radius = (radius * 16.0).ceil() / 16.0;
+
+        let up = if sun_direction.abs().y > 0.99 {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        };
+        let eye = center + sun_direction * (radius + CASTER_DISTANCE);
+        let view = Mat4::look_at_rh(eye, center, up);
+        let mut projection = Mat4::orthographic_rh(
+            -radius,
+            radius,
+            -radius,
+            radius,
+            0.0,
+            2.0 * radius + CASTER_DISTANCE,
+        );
+
+        // Snap the world origin to a texel, which moves the whole cascade in whole texels
+        let texels = SHADOW_MAP_SIZE as f32 / 2.0;
+        let origin = (projection * view * Vec4::W).truncate().truncate() * texels;
+        let offset = (origin.round() - origin) / texels;
+        projection.w_axis.x += offset.x;
+        projection.w_axis.y += offset.y;
+
+        projection * view
+    }
+}
@@ -0,0 +1,24 @@
+// Depth-only pass drawing the faces from the sun into one cascade of the shadow map
+
+struct Camera {
+    view_projection: mat4x4<f32>,
+}
+
+// The light view and projection of the cascade being drawn
+@group(1)
+@binding(0)
+var<uniform> camera: Camera;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return camera.view_projection * vec4<f32>(position, 1.0);
+}
+
+// Builds the vertices straight from the face buffer, as `vs_pull` in shader.wgsl
+@vertex
+fn vs_pull(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let face = index / 6;
+    let vertices = face_vertices(face, face_direction(face));
+    let corner = quad_corner(vertices, index % 6);
+    return camera.view_projection * vec4<f32>(vertices.positions[corner], 1.0);
+}
@@ -0,0 +1,79 @@
+use crate::{
+    camera::CameraBinding,
+    light::LightingBinding,
+    render::{DEPTH_FORMAT, create_shader_module},
+};
+
+/// Background of the main render pass: a full-screen triangle shaded with the sky gradient,
+/// which the faces and their fog are drawn over.
+#[derive(Debug)]
+pub struct Sky {
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Sky {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        lighting_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let shader = create_shader_module(
+            device,
+            "sky.wgsl",
+            &[include_str!("./lighting.wgsl"), include_str!("./sky.wgsl")],
+        );
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sky Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, lighting_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sky Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            // Shares the pass with the faces, but leaves the cleared depth to them
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        Self { pipeline }
+    }
+
+    /// Fills the render target with the sky, from the camera's point of view.
+    pub fn draw(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        camera: &CameraBinding,
+        lighting: &LightingBinding,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera.bind_group(), &[]);
+        render_pass.set_bind_group(1, lighting.bind_group(), &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
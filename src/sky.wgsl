@@ -0,0 +1,40 @@
+// Fills the background with the sky, before the faces are drawn over it
+
+struct Camera {
+    view_projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    position: vec3<f32>,
+}
+
+@group(0)
+@binding(0)
+var<uniform> camera: Camera;
+
+@group(1)
+@binding(0)
+var<uniform> lighting: Lighting;
+
+struct SkyOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+// One triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> SkyOutput {
+    let ndc = vec2<f32>(f32(index / 2) * 4.0 - 1.0, f32(index % 2) * 4.0 - 1.0);
+    var out: SkyOutput;
+    out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+@fragment
+fn fs_main(in: SkyOutput) -> @location(0) vec4<f32> {
+    // The view ray through the pixel, from the near to the far plane, which works for both
+    // perspective and orthographic projections
+    let near = camera.inverse_view_projection * vec4<f32>(in.ndc, 0.0, 1.0);
+    let far = camera.inverse_view_projection * vec4<f32>(in.ndc, 1.0, 1.0);
+    let direction = normalize(far.xyz / far.w - near.xyz / near.w);
+    return vec4<f32>(sky_color(lighting, direction), 1.0);
+}
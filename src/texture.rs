@@ -0,0 +1,190 @@
+use std::num::NonZero;
+
+use anyhow::ensure;
+use image::{RgbaImage, imageops::FilterType};
+
+/// Edge length of one block texture in pixels.
+pub const TILE_SIZE: u32 = 16;
+
+/// Atlas layout laid out as `Atlas` in `shader.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct AtlasUniform {
+    columns: u32,
+    _padding: [u32; 3],
+}
+
+/// Block textures from a grid of tiles, addressed by the `u` and `v` of a `TextureCoord`.
+///
+/// Each tile gets its own layer of a 2D texture array rather than sharing one texture, so
+/// neither filtering nor mipmapping can bleed neighbouring tiles into it, and tiles repeat
+/// across greedy-meshed faces.
+#[derive(Debug)]
+pub struct TextureAtlas {
+    bind_group: wgpu::BindGroup,
+}
+
+impl TextureAtlas {
+    pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Texture Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZero::new(std::mem::size_of::<AtlasUniform>() as u64),
+                    },
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    /// Uploads an atlas image whose sides are multiples of `TILE_SIZE`.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        image: &RgbaImage,
+    ) -> anyhow::Result<Self> {
+        let (width, height) = image.dimensions();
+        ensure!(
+            width > 0 && height > 0 && width % TILE_SIZE == 0 && height % TILE_SIZE == 0,
+            "{width}x{height} pixels is not a grid of {TILE_SIZE}x{TILE_SIZE} tiles"
+        );
+        let (columns, rows) = (width / TILE_SIZE, height / TILE_SIZE);
+        let layers = columns * rows;
+        // The GL backend guesses the view dimension from the layer count, taking one layer for a
+        // plain 2D texture and six for a cube map, so the array is padded past those
+        let texture_layers = match layers {
+            1 | 6 => layers + 1,
+            _ => layers,
+        };
+        let max_layers = device.limits().max_texture_array_layers;
+        ensure!(
+            texture_layers <= max_layers,
+            "{layers} tiles exceed the limit of {max_layers} texture layers"
+        );
+
+        let mip_level_count = TILE_SIZE.ilog2() + 1;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Texture Atlas"),
+            size: wgpu::Extent3d {
+                width: TILE_SIZE,
+                height: TILE_SIZE,
+                depth_or_array_layers: texture_layers,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for layer in 0..layers {
+            let (x, y) = (layer % columns * TILE_SIZE, layer / columns * TILE_SIZE);
+            let mut mip = image::imageops::crop_imm(image, x, y, TILE_SIZE, TILE_SIZE).to_image();
+            for mip_level in 0..mip_level_count {
+                if mip_level > 0 {
+                    let size = mip.width() / 2;
+                    mip = image::imageops::resize(&mip, size, size, FilterType::Triangle);
+                }
+                queue.write_texture(
+                    wgpu::TexelCopyTextureInfo {
+                        texture: &texture,
+                        mip_level,
+                        origin: wgpu::Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: layer,
+                        },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    &mip,
+                    wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(4 * mip.width()),
+                        rows_per_image: None,
+                    },
+                    wgpu::Extent3d {
+                        width: mip.width(),
+                        height: mip.height(),
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        // Crisp up close, smooth in the distance
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Texture Atlas Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        use wgpu::util::DeviceExt;
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Texture Atlas Buffer"),
+            contents: bytemuck::bytes_of(&AtlasUniform {
+                columns,
+                _padding: [0; 3],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Texture Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        tracing::info!("Loaded texture atlas of {columns}x{rows} tiles");
+
+        Ok(Self { bind_group })
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
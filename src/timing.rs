@@ -0,0 +1,179 @@
+use std::time::{Duration, Instant};
+
+/// Splits the time between frames into fixed simulation ticks, so the simulation runs at the
+/// same rate however often frames are drawn.
+#[derive(Debug)]
+pub struct Timestep {
+    /// Seconds simulated by each tick.
+    tick: f32,
+    /// Frame time not yet simulated, less than a tick once `advance` returns.
+    accumulator: f32,
+    /// Stops the simulation clock, so only ticks queued by `step` run.
+    pub paused: bool,
+    /// Ticks queued by `step` while paused.
+    steps: u32,
+}
+
+impl Timestep {
+    pub fn new(ticks_per_second: u32) -> Self {
+        Self {
+            tick: 1.0 / ticks_per_second as f32,
+            accumulator: 0.0,
+            paused: false,
+            steps: 0,
+        }
+    }
+
+    /// Seconds simulated by each tick.
+    pub fn tick(&self) -> f32 {
+        self.tick
+    }
+
+    /// Adds a frame of `dt` seconds and returns how many ticks are due. While paused the frame
+    /// is dropped and only the ticks queued by `step` are due.
+    pub fn advance(&mut self, dt: f32) -> u32 {
+        if self.paused {
+            return std::mem::take(&mut self.steps);
+        }
+        self.accumulator += dt;
+        let ticks = (self.accumulator / self.tick).floor();
+        self.accumulator -= ticks * self.tick;
+        ticks as u32
+    }
+
+    /// How far the frame is from the last tick towards the next, from 0 to 1, for drawing the
+    /// simulation between its last two states.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.tick).clamp(0.0, 1.0)
+    }
+
+    /// Queues a single tick to run on the next frame while paused.
+    pub fn step(&mut self) {
+        if self.paused {
+            self.steps += 1;
+        }
+    }
+}
+
+/// Holds frames back to at most `max_fps` a second, leaving them to the present mode when
+/// unset.
+#[derive(Debug)]
+pub struct FrameLimiter {
+    max_fps: Option<u32>,
+    /// Earliest the next frame may start.
+    next_frame: Instant,
+}
+
+impl FrameLimiter {
+    pub fn new(max_fps: Option<u32>) -> Self {
+        Self {
+            max_fps,
+            next_frame: Instant::now(),
+        }
+    }
+
+    pub fn max_fps(&self) -> Option<u32> {
+        self.max_fps
+    }
+
+    pub fn set_max_fps(&mut self, max_fps: Option<u32>) {
+        self.max_fps = max_fps;
+        self.next_frame = Instant::now();
+    }
+
+    /// Earliest the next frame may start, or `None` if frames aren't capped.
+    pub fn next_frame(&self) -> Option<Instant> {
+        self.max_fps.map(|_| self.next_frame)
+    }
+
+    /// Schedules the frame after one starting now. Frames that start late push the schedule
+    /// back instead of being made up with a burst of frames.
+    pub fn frame_started(&mut self) {
+        if let Some(fps) = self.max_fps {
+            let interval = Duration::from_secs_f64(1.0 / fps.max(1) as f64);
+            self.next_frame = (self.next_frame + interval).max(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ticks and frames in powers of two, so the accumulated time stays exact
+
+    #[test]
+    fn ticks_accumulate_over_several_frames() {
+        let mut timestep = Timestep::new(4);
+        assert_eq!(timestep.tick(), 0.25);
+
+        assert_eq!(timestep.advance(0.125), 0);
+        assert_eq!(timestep.advance(0.125), 1);
+        assert_eq!(timestep.advance(0.375), 1);
+        assert_eq!(timestep.advance(0.625), 3);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn the_remainder_sets_alpha() {
+        let mut timestep = Timestep::new(4);
+        assert_eq!(timestep.advance(0.625), 2);
+        assert_eq!(timestep.alpha(), 0.5);
+        assert_eq!(timestep.advance(0.0625), 0);
+        assert_eq!(timestep.alpha(), 0.75);
+        // Carried over into the next tick
+        assert_eq!(timestep.advance(0.0625), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn paused_time_is_dropped() {
+        let mut timestep = Timestep::new(4);
+        timestep.advance(0.125);
+        timestep.paused = true;
+        assert_eq!(timestep.advance(10.0), 0);
+        assert_eq!(timestep.alpha(), 0.5);
+
+        timestep.paused = false;
+        assert_eq!(timestep.advance(0.125), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn step_queues_one_tick_while_paused() {
+        let mut timestep = Timestep::new(4);
+        timestep.step();
+        assert_eq!(timestep.advance(0.0), 0);
+
+        timestep.paused = true;
+        timestep.step();
+        assert_eq!(timestep.advance(1.0), 1);
+        assert_eq!(timestep.advance(1.0), 0);
+        timestep.step();
+        timestep.step();
+        assert_eq!(timestep.advance(0.0), 2);
+
+        // Steps are only taken while paused
+        timestep.paused = false;
+        assert_eq!(timestep.advance(0.0), 0);
+    }
+
+    #[test]
+    fn limiter_only_schedules_capped_frames() {
+        let mut limiter = FrameLimiter::new(None);
+        limiter.frame_started();
+        assert_eq!(limiter.next_frame(), None);
+
+        let before = Instant::now();
+        limiter.set_max_fps(Some(10));
+        assert_eq!(limiter.max_fps(), Some(10));
+        limiter.frame_started();
+        let interval = Duration::from_millis(100);
+        let next = limiter.next_frame().expect("frames are capped");
+        assert!(next >= before + interval);
+        assert!(next <= Instant::now() + interval);
+
+        limiter.set_max_fps(None);
+        assert_eq!(limiter.next_frame(), None);
+    }
+}
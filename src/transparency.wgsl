@@ -0,0 +1,31 @@
+// Blends the transparent faces accumulated by `fs_transparent` in shader.wgsl over the opaque
+// faces
+
+@group(0)
+@binding(0)
+var accum_texture: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var revealage_texture: texture_2d<f32>;
+
+// One triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let ndc = vec2<f32>(f32(index / 2) * 4.0 - 1.0, f32(index % 2) * 4.0 - 1.0);
+    return vec4<f32>(ndc, 1.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(position.xy);
+    let revealage = textureLoad(revealage_texture, pixel, 0).r;
+    if revealage >= 1.0 {
+        discard;
+    }
+
+    // Average color of the faces over the pixel, weighted towards the nearest
+    let accum = textureLoad(accum_texture, pixel, 0);
+    let color = accum.rgb / max(accum.a, 1e-5);
+    return vec4<f32>(color, 1.0 - revealage);
+}
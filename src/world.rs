@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use glam::{IVec3, Vec3};
+
+use crate::{
+    block::{AIR, BlockId, BlockRegistry},
+    chunk::{CHUNK_SIZE, Chunk, FaceLists, MeshMode, neighbour_offset},
+    lightmap::Light,
+};
+
+/// Position of a chunk in the world, measured in chunks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Chunk containing the block at the given world position.
+    pub fn of_block(x: i32, y: i32, z: i32) -> Self {
+        let size = CHUNK_SIZE as i32;
+        Self::new(x.div_euclid(size), y.div_euclid(size), z.div_euclid(size))
+    }
+
+    /// World position of the chunk's block at (0, 0, 0).
+    pub fn origin(&self) -> [i32; 3] {
+        let size = CHUNK_SIZE as i32;
+        [self.x * size, self.y * size, self.z * size]
+    }
+
+    pub fn offset(&self, [dx, dy, dz]: [i32; 3]) -> Self {
+        Self::new(self.x + dx, self.y + dy, self.z + dz)
+    }
+
+    /// World position of the middle of the chunk.
+    pub fn center(&self) -> Vec3 {
+        IVec3::from(self.origin()).as_vec3() + Vec3::splat(CHUNK_SIZE as f32 / 2.0)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct World {
+    chunks: HashMap<ChunkPos, Chunk>,
+}
+
+impl World {
+    pub fn chunk(&self, pos: ChunkPos) -> Option<&Chunk> {
+        self.chunks.get(&pos)
+    }
+
+    pub fn chunk_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&pos)
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = (&ChunkPos, &Chunk)> {
+        self.chunks.iter()
+    }
+
+    /// Block at a world position, air where no chunk is loaded.
+    pub fn get(&self, x: i32, y: i32, z: i32) -> BlockId {
+        let size = CHUNK_SIZE as i32;
+        self.chunk(ChunkPos::of_block(x, y, z))
+            .map(|chunk| {
+                chunk.get(
+                    x.rem_euclid(size) as usize,
+                    y.rem_euclid(size) as usize,
+                    z.rem_euclid(size) as usize,
+                )
+            })
+            .unwrap_or(AIR)
+    }
+
+    /// Light at a world position, open sky where no chunk is loaded.
+    pub fn light(&self, x: i32, y: i32, z: i32) -> Light {
+        let size = CHUNK_SIZE as i32;
+        self.chunk(ChunkPos::of_block(x, y, z))
+            .map(|chunk| {
+                chunk.light(
+                    x.rem_euclid(size) as usize,
+                    y.rem_euclid(size) as usize,
+                    z.rem_euclid(size) as usize,
+                )
+            })
+            .unwrap_or(Light::SKY)
+    }
+
+    /// Sets a block at a world position, creating its chunk if needed. Light is left as it is,
+    /// see `lightmap::set_block` to update it too.
+    pub fn set(&mut self, x: i32, y: i32, z: i32, block: BlockId) {
+        let size = CHUNK_SIZE as i32;
+        self.chunks
+            .entry(ChunkPos::of_block(x, y, z))
+            .or_default()
+            .set(
+                x.rem_euclid(size) as usize,
+                y.rem_euclid(size) as usize,
+                z.rem_euclid(size) as usize,
+                block,
+            );
+    }
+
+    /// Generates the faces of a chunk, culling and occluding against its loaded neighbours.
+    pub fn face_data(&self, pos: ChunkPos, registry: &BlockRegistry, mode: MeshMode) -> FaceLists {
+        let Some(chunk) = self.chunk(pos) else {
+            return Default::default();
+        };
+        let neighbours =
+            std::array::from_fn(|index| self.chunk(pos.offset(neighbour_offset(index))));
+        chunk.face_data(registry, &neighbours, mode)
+    }
+}